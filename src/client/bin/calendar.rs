@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+use chrono::{Datelike, NaiveDate, TimeDelta, Weekday};
+use ratatui::style::{Style, Stylize};
+use ratatui::text::{Line, Span};
+use crate::theme::{Theme, SHADES};
+
+// from no plays to the busiest day of the year
+const LEVELS: usize = 5;
+
+const WEEKDAYS: [&str; 7] = ["Mon", "   ", "Wed", "   ", "Fri", "   ", "Sun"];
+
+// each week is a column two characters wide
+const CELL_WIDTH: usize = 2;
+
+pub fn first_day(year: i32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(year, 1, 1).unwrap_or_default()
+}
+
+pub fn last_day(year: i32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(year, 12, 31).unwrap_or_default()
+}
+
+// the same day in another year, the 28th for the 29th of February
+pub fn same_day_in(date: NaiveDate, year: i32) -> NaiveDate {
+    date.with_year(year)
+        .or_else(|| NaiveDate::from_ymd_opt(year, date.month(), 28))
+        .unwrap_or_else(|| first_day(year))
+}
+
+// the Monday the first column starts on
+fn first_week(year: i32) -> NaiveDate {
+    let first = first_day(year);
+    first - TimeDelta::days(first.weekday().num_days_from_monday() as i64)
+}
+
+fn level(value: u64, max: u64) -> usize {
+    if value == 0 || max == 0 {
+        return 0;
+    }
+    // the remaining levels split 1..=max into quarters
+    let quarter = ((value - 1) * 4 / max) as usize;
+    (quarter + 1).min(LEVELS - 1)
+}
+
+// coloured squares, or shades when the theme has no colours
+fn cell(level: usize, theme: &Theme) -> Span<'static> {
+    match theme.colour {
+        true => Span::styled("■ ", Style::from(theme.levels[level])),
+        false => Span::raw(SHADES[level]),
+    }
+}
+
+// a row of month names above the weeks they start in, then a row per weekday
+pub fn heatmap(year: i32, values: &HashMap<NaiveDate, u64>, cursor: NaiveDate, theme: &Theme) -> Vec<Line<'static>> {
+    let start = first_week(year);
+    let weeks = ((last_day(year) - start).num_days() / 7 + 1) as usize;
+    let max = values.values().copied().max().unwrap_or_default();
+
+    let mut months = " ".repeat(4 + weeks * CELL_WIDTH);
+    for month in 1..=12 {
+        let Some(first) = NaiveDate::from_ymd_opt(year, month, 1) else {
+            continue;
+        };
+        let column = 4 + (first - start).num_days() as usize / 7 * CELL_WIDTH;
+        let name = first.format("%b").to_string();
+        months.replace_range(column..column + name.len(), &name);
+    }
+
+    let mut lines = vec!(Line::from(months.trim_end().to_string()));
+
+    for (row, weekday) in WEEKDAYS.iter().enumerate() {
+        let mut spans = vec!(Span::raw(format!("{} ", weekday)));
+        for week in 0..weeks {
+            let date = start + TimeDelta::days((week * 7 + row) as i64);
+            if date.year() != year {
+                spans.push(Span::raw(" ".repeat(CELL_WIDTH)));
+                continue;
+            }
+
+            let cell = cell(level(values.get(&date).copied().unwrap_or_default(), max), theme);
+            spans.push(match date == cursor {
+                true if theme.colour => Span::styled("▣ ", cell.style.reversed()),
+                true => cell.reversed(),
+                false => cell,
+            });
+        }
+        lines.push(Line::from(spans));
+    }
+
+    let mut legend = vec!(Span::raw("    Less "));
+    legend.extend((0..LEVELS).map(|level| cell(level, theme)));
+    legend.push(Span::raw("More"));
+    lines.push(Line::default());
+    lines.push(Line::from(legend));
+
+    lines
+}
+
+pub fn weekday_name(date: NaiveDate) -> &'static str {
+    match date.weekday() {
+        Weekday::Mon => "Monday",
+        Weekday::Tue => "Tuesday",
+        Weekday::Wed => "Wednesday",
+        Weekday::Thu => "Thursday",
+        Weekday::Fri => "Friday",
+        Weekday::Sat => "Saturday",
+        Weekday::Sun => "Sunday",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::theme::Preset;
+
+    fn date(text: &str) -> NaiveDate {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn levels_split_into_quarters() {
+        assert_eq!(level(0, 8), 0);
+        assert_eq!(level(1, 8), 1);
+        assert_eq!(level(2, 8), 1);
+        assert_eq!(level(3, 8), 2);
+        assert_eq!(level(8, 8), 4);
+    }
+
+    #[test]
+    fn weeks_start_on_monday() {
+        // 2024-01-01 was a Monday, 2023-01-01 a Sunday
+        assert_eq!(first_week(2024), date("2024-01-01"));
+        assert_eq!(first_week(2023), date("2022-12-26"));
+    }
+
+    #[test]
+    fn leap_day_moves_to_the_28th() {
+        assert_eq!(same_day_in(date("2024-02-29"), 2023), date("2023-02-28"));
+        assert_eq!(same_day_in(date("2024-03-15"), 2023), date("2023-03-15"));
+    }
+
+    #[test]
+    fn a_row_per_weekday() {
+        let lines = heatmap(2024, &HashMap::new(), date("2024-01-01"), &Theme::preset(Preset::Default));
+        // months, 7 weekdays, a blank line and the legend
+        assert_eq!(lines.len(), 10);
+        assert!(lines[0].to_string().starts_with("    Jan"));
+    }
+
+    #[test]
+    fn shades_without_colour() {
+        let values = HashMap::from([(date("2024-01-01"), 4), (date("2024-01-08"), 1)]);
+        let lines = heatmap(2024, &values, date("2024-01-15"), &Theme::preset(Preset::NoColor));
+
+        assert!(lines[1].to_string().starts_with("Mon █ ░ · "));
+        assert_eq!(lines[9].to_string(), "    Less · ░ ▒ ▓ █ More");
+    }
+}
@@ -0,0 +1,277 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use mpressed::config::Config;
+
+// what a key does, some only in certain tabs or groupings
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Action {
+    Quit,
+    NextTab,
+    PrevTab,
+    Refresh,
+    Player,
+    Device,
+    Search,
+    NextMatch,
+    PrevMatch,
+    Mouse,
+    Up,
+    Down,
+    Left,
+    Right,
+    Top,
+    Bottom,
+    HalfPageUp,
+    HalfPageDown,
+    Select,
+    Back,
+    // compares an artist in the Artist grouping, reverses a key in the Sorting tab
+    Toggle,
+    Metric,
+    SortColumn,
+    SortDirection,
+    Period,
+    Chart,
+    PrevYear,
+    NextYear,
+    Time,
+}
+
+impl Action {
+    // the name in the [keys] section and the keys bound by default
+    const ALL: [(Action, &'static str, &'static [&'static str]); 29] = [
+        (Action::Quit, "quit", &["q", "Esc"]),
+        (Action::NextTab, "next_tab", &["Tab"]),
+        (Action::PrevTab, "prev_tab", &["BackTab"]),
+        (Action::Refresh, "refresh", &["r"]),
+        (Action::Player, "player", &["p"]),
+        (Action::Device, "device", &["d"]),
+        (Action::Search, "search", &["/"]),
+        (Action::NextMatch, "next_match", &["n"]),
+        (Action::PrevMatch, "prev_match", &["N"]),
+        (Action::Mouse, "mouse", &["m"]),
+        (Action::Up, "up", &["Up"]),
+        (Action::Down, "down", &["Down"]),
+        (Action::Left, "left", &["Left"]),
+        (Action::Right, "right", &["Right"]),
+        (Action::Top, "top", &["PageUp"]),
+        (Action::Bottom, "bottom", &["PageDown"]),
+        (Action::HalfPageUp, "half_page_up", &[]),
+        (Action::HalfPageDown, "half_page_down", &[]),
+        (Action::Select, "select", &["Enter"]),
+        (Action::Back, "back", &["Backspace"]),
+        (Action::Toggle, "toggle", &["Space"]),
+        (Action::Metric, "metric", &["w"]),
+        (Action::SortColumn, "sort_column", &["s"]),
+        (Action::SortDirection, "sort_direction", &["S"]),
+        (Action::Period, "period", &["i"]),
+        (Action::Chart, "chart", &["c"]),
+        (Action::PrevYear, "prev_year", &["["]),
+        (Action::NextYear, "next_year", &["]"]),
+        (Action::Time, "time", &["t"]),
+    ];
+}
+
+// added to the defaults by preset = vim
+const VIM: [(Action, &str); 8] = [
+    (Action::Up, "k"),
+    (Action::Down, "j"),
+    (Action::Left, "h"),
+    (Action::Right, "l"),
+    (Action::Top, "g"),
+    (Action::Bottom, "G"),
+    (Action::HalfPageUp, "C-u"),
+    (Action::HalfPageDown, "C-d"),
+];
+
+// Shift is left out, it is already in the case of the character
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Key(KeyCode, KeyModifiers);
+
+impl Key {
+    fn from_event(event: KeyEvent) -> Self {
+        Key(event.code, event.modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT))
+    }
+}
+
+// as it is written in the config, but with arrows for the arrow keys
+impl Display for Key {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        if self.1.contains(KeyModifiers::CONTROL) {
+            write!(f, "C-")?;
+        }
+        if self.1.contains(KeyModifiers::ALT) {
+            write!(f, "A-")?;
+        }
+
+        match self.0 {
+            KeyCode::Char(' ') => write!(f, "Space"),
+            KeyCode::Char(char) => write!(f, "{}", char),
+            KeyCode::Up => write!(f, "↑"),
+            KeyCode::Down => write!(f, "↓"),
+            KeyCode::Left => write!(f, "←"),
+            KeyCode::Right => write!(f, "→"),
+            KeyCode::Backspace => write!(f, "⌫"),
+            code => write!(f, "{:?}", code),
+        }
+    }
+}
+
+// a character, a key name like PageUp or Space, optionally after C- for Control or A- for Alt
+pub fn parse_key(text: &str) -> Option<Key> {
+    let mut modifiers = KeyModifiers::NONE;
+    let mut text = text.trim();
+    loop {
+        if let Some(rest) = text.strip_prefix("C-").filter(|rest| !rest.is_empty()) {
+            modifiers |= KeyModifiers::CONTROL;
+            text = rest;
+        } else if let Some(rest) = text.strip_prefix("A-").filter(|rest| !rest.is_empty()) {
+            modifiers |= KeyModifiers::ALT;
+            text = rest;
+        } else {
+            break;
+        }
+    }
+
+    let mut chars = text.chars();
+    let code = match (chars.next(), chars.next()) {
+        (Some(char), None) => KeyCode::Char(char),
+        _ => match text.to_lowercase().as_str() {
+            "esc" => KeyCode::Esc,
+            "tab" => KeyCode::Tab,
+            "backtab" => KeyCode::BackTab,
+            "enter" => KeyCode::Enter,
+            "space" => KeyCode::Char(' '),
+            "comma" => KeyCode::Char(','),
+            "backspace" => KeyCode::Backspace,
+            "up" => KeyCode::Up,
+            "down" => KeyCode::Down,
+            "left" => KeyCode::Left,
+            "right" => KeyCode::Right,
+            "home" => KeyCode::Home,
+            "end" => KeyCode::End,
+            "pageup" => KeyCode::PageUp,
+            "pagedown" => KeyCode::PageDown,
+            _ => return None,
+        },
+    };
+
+    Some(Key(code, modifiers))
+}
+
+#[derive(Debug)]
+pub struct Keymap {
+    bindings: HashMap<Key, Action>,
+    // in the order they were given, for showing them
+    keys: HashMap<Action, Vec<Key>>,
+}
+
+impl Keymap {
+    // [keys] preset = vim adds vim style navigation, any action named in the section
+    // has its default keys replaced by the comma separated ones given
+    //
+    // [keys]
+    // preset = vim
+    // quit = q, C-c
+    pub fn from_config(config: &Config) -> Self {
+        let mut keys: HashMap<Action, Vec<Key>> = Action::ALL.iter()
+            .map(|(action, _, keys)| (*action, keys.iter().filter_map(|key| parse_key(key)).collect()))
+            .collect();
+
+        if config.get("keys", "preset").is_some_and(|preset| preset.eq_ignore_ascii_case("vim")) {
+            for (action, key) in VIM {
+                keys.entry(action).or_default().extend(parse_key(key));
+            }
+        }
+
+        let mut replaced = vec!();
+        for (name, value) in config.entries("keys") {
+            let Some((action, _, _)) = Action::ALL.iter().find(|(_, action_name, _)| *action_name == name) else {
+                continue;
+            };
+
+            // repeated names add to each other rather than to the defaults
+            let bound = keys.entry(*action).or_default();
+            if !replaced.contains(action) {
+                bound.clear();
+                replaced.push(*action);
+            }
+            bound.extend(value.split(',').filter_map(parse_key));
+        }
+
+        // a key bound to several actions does the one set in the config, or the last one listed
+        let mut bindings = HashMap::new();
+        let order = Action::ALL.iter()
+            .map(|(action, _, _)| *action)
+            .filter(|action| !replaced.contains(action))
+            .chain(replaced.iter().copied());
+        for action in order {
+            for key in &keys[&action] {
+                bindings.insert(*key, action);
+            }
+        }
+
+        Self { bindings, keys }
+    }
+
+    pub fn action(&self, event: KeyEvent) -> Option<Action> {
+        self.bindings.get(&Key::from_event(event)).copied()
+    }
+
+    // the first key that does the action, None when another action took all of them
+    pub fn key(&self, action: Action) -> Option<Key> {
+        self.keys.get(&action)?
+            .iter()
+            .find(|key| self.bindings.get(key) == Some(&action))
+            .copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent::new(code, modifiers)
+    }
+
+    #[test]
+    fn parses_keys() {
+        assert_eq!(parse_key("j"), Some(Key(KeyCode::Char('j'), KeyModifiers::NONE)));
+        assert_eq!(parse_key(" PageUp "), Some(Key(KeyCode::PageUp, KeyModifiers::NONE)));
+        assert_eq!(parse_key("C-d"), Some(Key(KeyCode::Char('d'), KeyModifiers::CONTROL)));
+        assert_eq!(parse_key("C--"), Some(Key(KeyCode::Char('-'), KeyModifiers::CONTROL)));
+        assert_eq!(parse_key("C-"), None);
+        assert_eq!(parse_key("Space"), Some(Key(KeyCode::Char(' '), KeyModifiers::NONE)));
+        assert_eq!(parse_key("Nope"), None);
+    }
+
+    #[test]
+    fn defaults() {
+        let keymap = Keymap::from_config(&Config::default());
+
+        assert_eq!(keymap.action(event(KeyCode::Esc, KeyModifiers::NONE)), Some(Action::Quit));
+        assert_eq!(keymap.action(event(KeyCode::Char('S'), KeyModifiers::SHIFT)), Some(Action::SortDirection));
+        assert_eq!(keymap.action(event(KeyCode::Char('j'), KeyModifiers::NONE)), None);
+    }
+
+    #[test]
+    fn vim_preset_and_overrides() {
+        let keymap = Keymap::from_config(&Config::parse("[keys]\npreset = vim\nquit = C-c, x\nquit = Q\nup = d"));
+
+        assert_eq!(keymap.action(event(KeyCode::Char('j'), KeyModifiers::NONE)), Some(Action::Down));
+        assert_eq!(keymap.action(event(KeyCode::Char('d'), KeyModifiers::CONTROL)), Some(Action::HalfPageDown));
+        assert_eq!(keymap.action(event(KeyCode::Char('d'), KeyModifiers::NONE)), Some(Action::Up));
+        assert_eq!(keymap.action(event(KeyCode::Char('c'), KeyModifiers::CONTROL)), Some(Action::Quit));
+        assert_eq!(keymap.action(event(KeyCode::Char('Q'), KeyModifiers::SHIFT)), Some(Action::Quit));
+        assert_eq!(keymap.action(event(KeyCode::Char('q'), KeyModifiers::NONE)), None);
+        assert_eq!(keymap.action(event(KeyCode::Up, KeyModifiers::NONE)), None);
+
+        let key = |action| keymap.key(action).map(|key| key.to_string());
+        assert_eq!(key(Action::Quit), Some("C-c".to_string()));
+        assert_eq!(key(Action::Down), Some("↓".to_string()));
+        assert_eq!(key(Action::Toggle), Some("Space".to_string()));
+        assert_eq!(key(Action::Device), None);
+    }
+}
@@ -0,0 +1,411 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+use chrono::{Months, NaiveDate};
+use mpressed::period::{period_totals, Period, PeriodStat};
+use mpressed::stats::{DateStat, Filter, SongMonth, SongSummary, SourceStat, Stats};
+use mpressed::discovery::{discoveries, Discoveries, FirstPlays};
+use mpressed::wrapped::{wrapped, Wrapped};
+use crate::calendar::{first_day, last_day};
+use crate::metric::{set_shares, Measures};
+use crate::{SongDataAlbum, SongDataArtist, SongDataDate, SongDataNone, SongDataSource};
+
+// the most played artists charted over time when none have been picked
+const TREND_ARTISTS: usize = 5;
+
+// what can be reloaded on its own
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Part {
+    // every grouping with a table
+    Tables,
+    Calendar,
+    Trend,
+    Wrapped,
+    // the highlighted song's detail panel, asked for when it's shown
+    SongDetail,
+}
+
+impl Part {
+    pub const ALL: [Part; 4] = [Part::Tables, Part::Calendar, Part::Trend, Part::Wrapped];
+}
+
+// everything the queries depend on, copied from the TUI when asking for them
+#[derive(Clone, Debug, Default)]
+pub struct Query {
+    pub filter: Filter,
+    pub date_period: Period,
+    // the whole year is loaded, whatever range is selected
+    pub calendar_year: i32,
+    pub trend_shown: bool,
+    pub trend_period: Period,
+    // artists picked for the chart, the top ones are used when empty
+    pub compared: Vec<String>,
+    pub wrapped_year: i32,
+    // a week or a month
+    pub discovery_period: Period,
+    // the highlighted song in the None grouping
+    pub song: Option<i64>,
+}
+
+#[derive(Debug, Default)]
+pub struct Tables {
+    pub none: Vec<SongDataNone>,
+    pub date: Vec<SongDataDate>,
+    pub artist: Vec<SongDataArtist>,
+    pub album: Vec<SongDataAlbum>,
+    pub player: Vec<SongDataSource>,
+    pub device: Vec<SongDataSource>,
+    pub discovery: Vec<Discoveries>,
+}
+
+// None for the parts that weren't asked for, or were asked for again since
+#[derive(Debug, Default)]
+pub struct Data {
+    pub tables: Option<Tables>,
+    pub calendar: Option<HashMap<NaiveDate, DateStat>>,
+    // plays per period of each artist in the Artist grouping's chart
+    pub trend: Option<Vec<(String, Vec<PeriodStat>)>>,
+    pub wrapped: Option<Wrapped>,
+    pub song_detail: Option<SongDetail>,
+}
+
+// history of the highlighted song in the None grouping
+#[derive(Debug)]
+pub struct SongDetail {
+    pub id: i64,
+    pub summary: Option<SongSummary>,
+    // every month from the first play to the last, including ones without plays
+    pub months: Vec<SongMonth>,
+}
+
+pub fn load(stats: &Stats, query: &Query, parts: &HashSet<Part>) -> rusqlite::Result<Data> {
+    let mut data = Data::default();
+    for part in parts {
+        match part {
+            Part::Tables => data.tables = Some(tables(stats, query)?),
+            Part::Calendar => data.calendar = Some(calendar(stats, query)?),
+            Part::Trend => data.trend = Some(trend(stats, query)?),
+            Part::Wrapped => data.wrapped = Some(wrapped(stats, query.wrapped_year, &query.filter)?),
+            Part::SongDetail => data.song_detail = query.song.map(|id| song_detail(stats, query, id)).transpose()?,
+        }
+    }
+    Ok(data)
+}
+
+fn tables(stats: &Stats, query: &Query) -> rusqlite::Result<Tables> {
+    let filter = &query.filter;
+
+    let first_plays = FirstPlays::load(stats, filter)?;
+    let song_first_heard: HashMap<i64, &String> = first_plays.songs.iter()
+        .map(|first| (first.id, &first.first_played))
+        .collect();
+    let artist_first_heard: HashMap<&String, &String> = first_plays.artists.iter()
+        .map(|first| (&first.artist, &first.first_played))
+        .collect();
+    let album_first_heard: HashMap<(&String, &String), &String> = first_plays.albums.iter()
+        .map(|first| ((&first.artist, &first.album), &first.first_played))
+        .collect();
+
+    let mut none: Vec<SongDataNone> = stats.top_songs(filter)?
+        .into_iter()
+        .map(|data| {
+            let first_heard = song_first_heard.get(&data.id).map(|date| date.to_string()).unwrap_or_default();
+            SongDataNone::new(data.id, data.song.artist, data.song.album, data.song.title,
+                              Measures::new(data.plays, data.listening_time, 1, data.recency), first_heard)
+        })
+        .collect();
+    set_shares(none.iter_mut().map(|data| &mut data.measures));
+
+    // periods without plays are included so the chart's x axis has no gaps
+    let dates = stats.date_totals(&filter.clone().limit(None))?;
+    let date = period_totals(&dates, query.date_period, filter.from, filter.to)
+        .into_iter()
+        .map(|data| SongDataDate::new(data.start, data.label, data.plays))
+        .collect();
+
+    let mut artist: Vec<SongDataArtist> = stats.top_artists(filter)?
+        .into_iter()
+        .map(|data| {
+            let first_heard = artist_first_heard.get(&data.artist).map(|date| date.to_string()).unwrap_or_default();
+            let measures = Measures::new(data.plays, data.listening_time, data.songs_played, data.recency).weighted_by(data.songs);
+            SongDataArtist::new(data.artist, measures, first_heard)
+        })
+        .collect();
+    set_shares(artist.iter_mut().map(|data| &mut data.measures));
+
+    let mut album: Vec<SongDataAlbum> = stats.top_albums(filter)?
+        .into_iter()
+        .map(|data| {
+            let first_heard = album_first_heard.get(&(&data.artist, &data.album)).map(|date| date.to_string()).unwrap_or_default();
+            let measures = Measures::new(data.plays, data.listening_time, data.songs_played, data.recency).weighted_by(data.songs);
+            SongDataAlbum::new(data.artist, data.album, measures, first_heard)
+        })
+        .collect();
+    set_shares(album.iter_mut().map(|data| &mut data.measures));
+
+    let source = |data: Vec<SourceStat>| data.into_iter()
+        .map(|data| SongDataSource::new(data.source, data.plays))
+        .collect();
+
+    Ok(Tables {
+        none,
+        date,
+        artist,
+        album,
+        player: source(stats.players(filter)?),
+        device: source(stats.devices(filter)?),
+        discovery: discoveries(&first_plays, filter, query.discovery_period),
+    })
+}
+
+fn calendar(stats: &Stats, query: &Query) -> rusqlite::Result<HashMap<NaiveDate, DateStat>> {
+    let filter = query.filter.clone()
+        .range(Some(first_day(query.calendar_year)), Some(last_day(query.calendar_year)))
+        .limit(None);
+
+    Ok(stats.date_totals(&filter)?
+        .into_iter()
+        .filter_map(|data| Some((data.date.parse().ok()?, data)))
+        .collect())
+}
+
+fn trend(stats: &Stats, query: &Query) -> rusqlite::Result<Vec<(String, Vec<PeriodStat>)>> {
+    if !query.trend_shown {
+        return Ok(vec!());
+    }
+
+    let artists = match query.compared.is_empty() {
+        true => stats.top_artists(&query.filter.clone().limit(Some(TREND_ARTISTS)))?
+            .into_iter()
+            .map(|data| data.artist)
+            .collect(),
+        false => query.compared.clone(),
+    };
+
+    let dates = artists.into_iter()
+        .map(|artist| {
+            let dates = stats.date_totals(&query.filter.clone().artist(Some(artist.clone())).limit(None))?;
+            Ok((artist, dates))
+        })
+        .collect::<rusqlite::Result<Vec<(String, Vec<DateStat>)>>>()?;
+
+    // every series covers the same periods so they line up
+    let all = dates.iter().flat_map(|(_, dates)| dates.iter());
+    let from = query.filter.from.or(all.clone().filter_map(|data| data.date.parse().ok()).min());
+    let to = query.filter.to.or(all.filter_map(|data| data.date.parse().ok()).max());
+
+    Ok(dates.into_iter()
+        .map(|(artist, dates)| (artist, period_totals(&dates, query.trend_period, from, to)))
+        .collect())
+}
+
+// the song's whole history, only the player and device filters apply
+fn song_detail(stats: &Stats, query: &Query, id: i64) -> rusqlite::Result<SongDetail> {
+    let filter = Filter::default()
+        .player(query.filter.player.clone())
+        .device(query.filter.device.clone())
+        .song(Some(id));
+
+    Ok(SongDetail {
+        id,
+        summary: stats.song_summary(&filter)?,
+        months: fill_months(stats.song_months(&filter)?),
+    })
+}
+
+// zero plays and rank for the months the song wasn't played
+fn fill_months(months: Vec<SongMonth>) -> Vec<SongMonth> {
+    let first_day = |month: &SongMonth| NaiveDate::parse_from_str(&format!("{}-01", month.month), "%Y-%m-%d").ok();
+    let (Some(mut current), Some(last)) = (months.first().and_then(first_day), months.last().and_then(first_day)) else {
+        return months;
+    };
+
+    let mut months = months.into_iter().peekable();
+    let mut filled = vec!();
+    while current <= last {
+        let month = current.format("%Y-%m").to_string();
+        match months.next_if(|next| next.month == month) {
+            Some(next) => filled.push(next),
+            None => filled.push(SongMonth { month, plays: 0, rank: 0 }),
+        }
+        current = current + Months::new(1);
+    }
+    filled
+}
+
+#[derive(Debug)]
+struct Request {
+    generation: u64,
+    query: Query,
+    parts: HashSet<Part>,
+}
+
+// runs the queries on a thread of its own so the TUI keeps drawing and taking keys
+#[derive(Debug)]
+pub struct Loader {
+    requests: Sender<Request>,
+    loaded: Receiver<(u64, rusqlite::Result<Data>)>,
+    generation: u64,
+    // the generation each part was last asked for in, older answers for it are dropped
+    requested: HashMap<Part, u64>,
+    received: u64,
+}
+
+impl Loader {
+    pub fn spawn(stats: Stats) -> Self {
+        let (requests, queue) = channel::<Request>();
+        let (answers, loaded) = channel();
+
+        thread::spawn(move || {
+            while let Ok(mut request) = queue.recv() {
+                // only the latest query is current, the parts of anything queued before it are loaded for it
+                while let Ok(next) = queue.try_recv() {
+                    request.parts.extend(next.parts);
+                    request.query = next.query;
+                    request.generation = next.generation;
+                }
+
+                let data = load(&stats, &request.query, &request.parts);
+                if answers.send((request.generation, data)).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Self {
+            requests,
+            loaded,
+            generation: 0,
+            requested: HashMap::new(),
+            received: 0,
+        }
+    }
+
+    pub fn request(&mut self, query: Query, parts: &[Part]) {
+        self.generation += 1;
+        for part in parts {
+            self.requested.insert(*part, self.generation);
+        }
+
+        let request = Request {
+            generation: self.generation,
+            query,
+            parts: parts.iter().copied().collect(),
+        };
+        // a worker that is gone has panicked, which leaves nothing to do here
+        let _ = self.requests.send(request);
+    }
+
+    pub fn loading(&self) -> bool {
+        self.received < self.generation
+    }
+
+    // an answer if one is ready, without the parts that have been asked for again since
+    pub fn try_recv(&mut self) -> Option<rusqlite::Result<Data>> {
+        let (generation, data) = self.loaded.try_recv().ok()?;
+        self.received = generation;
+
+        let current = |part| self.requested.get(&part).is_some_and(|requested| *requested <= generation);
+        Some(data.map(|data| Data {
+            tables: data.tables.filter(|_| current(Part::Tables)),
+            calendar: data.calendar.filter(|_| current(Part::Calendar)),
+            trend: data.trend.filter(|_| current(Part::Trend)),
+            wrapped: data.wrapped.filter(|_| current(Part::Wrapped)),
+            song_detail: data.song_detail.filter(|_| current(Part::SongDetail)),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+    use mpressed::SongData;
+    use super::*;
+
+    fn stats() -> Stats {
+        let stats = Stats::open_in_memory().unwrap();
+        let song = |artist: &str, title: &str| SongData {
+            artist: artist.to_string(),
+            album: "Album".to_string(),
+            title: title.to_string(),
+            ..SongData::default()
+        };
+
+        stats.record_play(&song("a", "x"), "2024-01-01", "VLC", "desktop", None).unwrap();
+        stats.record_play(&song("a", "x"), "2024-01-02", "VLC", "desktop", None).unwrap();
+        stats.record_play(&song("b", "y"), "2024-01-02", "mpv", "laptop", None).unwrap();
+        stats
+    }
+
+    fn wait_result(loader: &mut Loader) -> rusqlite::Result<Data> {
+        let start = Instant::now();
+        while start.elapsed() < Duration::from_secs(5) {
+            if let Some(data) = loader.try_recv() {
+                return data;
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+        panic!("nothing was loaded");
+    }
+
+    fn wait(loader: &mut Loader) -> Data {
+        wait_result(loader).unwrap()
+    }
+
+    #[test]
+    fn loads_the_parts_asked_for() {
+        let query = Query { calendar_year: 2024, ..Query::default() };
+        let data = load(&stats(), &query, &[Part::Tables, Part::Calendar].into()).unwrap();
+
+        let tables = data.tables.unwrap();
+        assert_eq!(tables.none.len(), 2);
+        assert_eq!(tables.artist.iter().map(|data| data.measures.plays).sum::<u32>(), 3);
+        assert!((tables.artist.iter().map(|data| data.measures.share).sum::<f64>() - 1.0).abs() < 1e-9);
+        assert_eq!(tables.player.len(), 2);
+        assert_eq!(data.calendar.unwrap().len(), 2);
+        assert!(data.trend.is_none());
+        assert!(data.wrapped.is_none());
+    }
+
+    #[test]
+    fn loads_the_highlighted_songs_detail() {
+        let query = Query { song: Some(1), ..Query::default() };
+        let detail = load(&stats(), &query, &[Part::SongDetail].into()).unwrap().song_detail.unwrap();
+
+        assert_eq!(detail.id, 1);
+        assert_eq!(detail.summary.unwrap().plays, 2);
+        assert_eq!(detail.months.len(), 1);
+    }
+
+    #[test]
+    fn drops_answers_asked_for_again() {
+        let mut loader = Loader::spawn(stats());
+        loader.request(Query::default(), &[Part::Tables]);
+        let data = wait(&mut loader);
+        assert!(!loader.loading());
+        assert_eq!(data.tables.unwrap().none.len(), 2);
+
+        // the first answer can't be the one filtered by artist, whether or not the two were merged
+        loader.request(Query::default(), &[Part::Tables, Part::Trend]);
+        loader.request(Query { filter: Filter::default().artist(Some("b".to_string())), ..Query::default() }, &[Part::Tables]);
+        assert!(loader.loading());
+
+        let mut tables = None;
+        while loader.loading() {
+            let data = wait(&mut loader);
+            tables = data.tables.or(tables);
+        }
+        assert_eq!(tables.unwrap().none.len(), 1);
+    }
+
+    #[test]
+    fn hands_errors_back() {
+        let stats = stats();
+        stats.connection().execute("DROP TABLE song_plays", []).unwrap();
+
+        let mut loader = Loader::spawn(stats);
+        loader.request(Query::default(), &[Part::Tables]);
+        assert!(wait_result(&mut loader).is_err());
+        assert!(!loader.loading());
+    }
+}
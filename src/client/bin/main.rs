@@ -1,44 +1,76 @@
-use std::collections::HashMap;
 use ratatui::backend::{Backend, CrosstermBackend};
-use ratatui::crossterm::event::{DisableMouseCapture, EnableMouseCapture, Event};
+use ratatui::crossterm::event::{DisableMouseCapture, EnableMouseCapture, Event, MouseButton, MouseEvent, MouseEventKind};
 use ratatui::crossterm::execute;
+use ratatui::crossterm::style::force_color_output;
 use ratatui::crossterm::terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen};
-use ratatui::layout::{Alignment, Constraint, Direction, Layout, Margin, Rect, Rows};
+use ratatui::layout::{Alignment, Constraint, Direction, Layout, Margin, Position, Rect};
 use ratatui::prelude::Color;
-use ratatui::style::{Modifier, Style, Stylize};
-use ratatui::text::{Line, Text, ToSpan};
+use ratatui::style::{Style, Stylize};
+use ratatui::text::{Line, Span, Text, ToSpan};
 use ratatui::widgets::block::Title;
-use ratatui::widgets::{Axis, BarChart, Block, BorderType, Cell, Chart, Dataset, GraphType, LegendPosition, LineGauge, List, ListState, Padding, Paragraph, Row, Scrollbar, ScrollbarOrientation, ScrollbarState, Table, TableState};
+use ratatui::widgets::{Axis, Bar, BarChart, BarGroup, Block, BorderType, Cell, Chart, Dataset, GraphType, LegendPosition, LineGauge, List, ListState, Padding, Paragraph, Row, Scrollbar, ScrollbarOrientation, ScrollbarState, Sparkline, Table, TableState};
 use ratatui::{crossterm::event::{self, KeyCode}, symbols, Frame, Terminal};
-use rusqlite::Connection;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::env;
 use std::io;
 use std::io::Result;
-use chrono::{DateTime, Utc};
-use strum::Display;
-use mpressed::get_db_path;
+use std::process::exit;
+use std::time::{Duration, Instant};
+use chrono::{Datelike, Local, NaiveDate, TimeDelta};
+use mpressed::{format_duration, get_db_path};
+use mpressed::config::Config;
+use mpressed::stats::{DateStat, Filter, Stats};
+use mpressed::now_playing::{now_playing, NowPlaying};
+use mpressed::pause::{pause_state, PauseState};
+use mpressed::period::{Period, PeriodStat};
+use mpressed::discovery::Discoveries;
+use mpressed::wrapped::{wrapped, Section, Wrapped};
+use calendar::{first_day, heatmap, last_day, same_day_in, weekday_name};
+use mouse::{column_at, fraction_at, graph_area, line_at, nearest, table_columns, visible_rows, Areas, HEADER_HEIGHT};
+use range::{format_bounds, parse_bounds, Range};
+use search::{fuzzy_match, highlight};
+use sort::{promote, sort_rows, Sort, SortDirection, SortRow};
+use keys::{Action, Keymap};
+use loader::{Data, Loader, Part, Query, SongDetail};
+use metric::{Measures, Metric};
+use theme::Theme;
+
+mod calendar;
+mod keys;
+mod loader;
+mod metric;
+mod mouse;
+mod range;
+mod search;
+mod sort;
+mod theme;
 
 #[derive(Debug, Default)]
 struct SongDataNone {
+    id: i64,
     artist: String,
     album: String,
     title: String,
-    plays_string: String,
-    plays: u32
+    measures: Measures,
+    // YYYY-MM-DD of the first play ever
+    first_heard: String,
 }
 
 impl SongDataNone {
-    pub fn new(artist: String, album: String, title: String, plays: u32) -> Self {
+    pub fn new(id: i64, artist: String, album: String, title: String, measures: Measures, first_heard: String) -> Self {
         Self {
+            id,
             artist,
             album,
             title,
-            plays_string: plays.to_string(),
-            plays,
+            measures,
+            first_heard,
         }
     }
 
-    pub fn ref_array(&self) -> [&str; 4] {
-        [&self.artist, &self.album, &self.title, &self.plays_string]
+    pub fn ref_array(&self) -> [&str; 3] {
+        [&self.artist, &self.album, &self.title]
     }
 
     pub fn artist(&self) -> &str {
@@ -53,29 +85,25 @@ impl SongDataNone {
         &self.title
     }
 
-    pub fn plays_string(&self) -> &str {
-        &self.plays_string
-    }
-
-    pub fn plays(&self) -> u32 {
-        self.plays
+    pub fn measures(&self) -> &Measures {
+        &self.measures
     }
 }
 
 #[derive(Clone, Debug, Default)]
 struct SongDataDate {
+    start: NaiveDate,
+    // the period start is in, see Period::label
     date: String,
     plays: u32,
-    plays_weighted: f32
-
 }
 
 impl SongDataDate {
-    pub fn new(date: String, plays: u32, plays_weighted: f32) -> Self {
+    pub fn new(start: NaiveDate, date: String, plays: u32) -> Self {
         Self {
+            start,
             date,
             plays,
-            plays_weighted
         }
     }
 }
@@ -83,33 +111,118 @@ impl SongDataDate {
 #[derive(Clone, Debug, Default)]
 struct SongDataArtist {
     artist: String,
-    plays: u32,
-    plays_weighted: f32
+    measures: Measures,
+    first_heard: String,
 }
 
 impl SongDataArtist {
-    pub fn new(artist: String, plays: u32, plays_weighted: f32) -> Self {
+    pub fn new(artist: String, measures: Measures, first_heard: String) -> Self {
         Self {
             artist,
-            plays,
-            plays_weighted
+            measures,
+            first_heard,
         }
     }
 }
 
 #[derive(Debug, Default)]
 struct SongDataAlbum {
+    artist: String,
     album: String,
-    plays: u32,
-    plays_weighted: f32
+    measures: Measures,
+    first_heard: String,
 }
 
 impl SongDataAlbum {
-    pub fn new(album: String, plays: u32, plays_weighted: f32) -> Self {
+    pub fn new(artist: String, album: String, measures: Measures, first_heard: String) -> Self {
         Self {
+            artist,
             album,
+            measures,
+            first_heard,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct SongDataSource {
+    source: String,
+    plays: u32,
+}
+
+impl SongDataSource {
+    pub fn new(source: String, plays: u32) -> Self {
+        Self {
+            source,
             plays,
-            plays_weighted
+        }
+    }
+}
+
+impl SortRow for SongDataNone {
+    fn cmp_by(&self, other: &Self, sort: Sort, metric: Metric) -> Ordering {
+        match sort {
+            Sort::Artist => self.artist().cmp(other.artist()),
+            Sort::Album => self.album().cmp(other.album()),
+            Sort::Title => self.title().cmp(other.title()),
+            Sort::Plays => metric.value(self.measures()).total_cmp(&metric.value(other.measures())),
+            Sort::FirstHeard => self.first_heard.cmp(&other.first_heard),
+            _ => Ordering::Equal,
+        }
+    }
+}
+
+impl SortRow for SongDataDate {
+    fn cmp_by(&self, other: &Self, sort: Sort, _metric: Metric) -> Ordering {
+        match sort {
+            Sort::Date => self.start.cmp(&other.start),
+            Sort::Plays => self.plays.cmp(&other.plays),
+            _ => Ordering::Equal,
+        }
+    }
+}
+
+impl SortRow for SongDataArtist {
+    fn cmp_by(&self, other: &Self, sort: Sort, metric: Metric) -> Ordering {
+        match sort {
+            Sort::Artist => self.artist.cmp(&other.artist),
+            Sort::Plays => metric.value(&self.measures).total_cmp(&metric.value(&other.measures)),
+            Sort::FirstHeard => self.first_heard.cmp(&other.first_heard),
+            _ => Ordering::Equal,
+        }
+    }
+}
+
+impl SortRow for SongDataAlbum {
+    fn cmp_by(&self, other: &Self, sort: Sort, metric: Metric) -> Ordering {
+        match sort {
+            Sort::Artist => self.artist.cmp(&other.artist),
+            Sort::Album => self.album.cmp(&other.album),
+            Sort::Plays => metric.value(&self.measures).total_cmp(&metric.value(&other.measures)),
+            Sort::FirstHeard => self.first_heard.cmp(&other.first_heard),
+            _ => Ordering::Equal,
+        }
+    }
+}
+
+impl SortRow for Discoveries {
+    fn cmp_by(&self, other: &Self, sort: Sort, _metric: Metric) -> Ordering {
+        match sort {
+            Sort::Date => self.start.cmp(&other.start),
+            Sort::Artist => self.artists.len().cmp(&other.artists.len()),
+            Sort::Album => self.albums.len().cmp(&other.albums.len()),
+            Sort::Title => self.songs.len().cmp(&other.songs.len()),
+            _ => Ordering::Equal,
+        }
+    }
+}
+
+impl SortRow for SongDataSource {
+    fn cmp_by(&self, other: &Self, sort: Sort, _metric: Metric) -> Ordering {
+        match sort {
+            Sort::Player | Sort::Device => source_name(&self.source).cmp(source_name(&other.source)),
+            Sort::Plays => self.plays.cmp(&other.plays),
+            _ => Ordering::Equal,
         }
     }
 }
@@ -119,28 +232,22 @@ enum SelectedTab {
     #[default]
     Table,
     Sort,
-    Group
-}
-
-#[derive(Debug, Default, Display)]
-enum Sort {
-    Artist,
-    Album,
-    Title,
-    #[default]
-    Plays,
+    Group,
+    Range,
 }
 
-#[derive(Debug, Default)]
-struct SortDirection(Sort, bool);
-
-#[derive(Debug, Default)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
 enum Group {
     #[default]
     None,
     Date,
+    Calendar,
     Artist,
     Album,
+    Player,
+    Device,
+    Wrapped,
+    Discovery,
 }
 
 impl SelectedTab {
@@ -148,235 +255,774 @@ impl SelectedTab {
         *self = match self {
             SelectedTab::Table => SelectedTab::Sort,
             SelectedTab::Group => SelectedTab::Table,
-            SelectedTab::Sort => SelectedTab::Group,
+            SelectedTab::Range => SelectedTab::Group,
+            SelectedTab::Sort => SelectedTab::Range,
         }
     }
 
     pub fn next(&mut self) {
         *self = match self {
             SelectedTab::Table => SelectedTab::Group,
-            SelectedTab::Group => SelectedTab::Sort,
+            SelectedTab::Group => SelectedTab::Range,
+            SelectedTab::Range => SelectedTab::Sort,
             SelectedTab::Sort => SelectedTab::Table,
         }
     }
 }
 
 impl Group {
+    const ALL: [Group; 9] = [Group::None, Group::Date, Group::Calendar, Group::Artist, Group::Album, Group::Player, Group::Device, Group::Wrapped, Group::Discovery];
+
+    // in the order they appear in the table
+    pub fn columns(self) -> &'static [Sort] {
+        match self {
+            Group::None => &[Sort::Artist, Sort::Album, Sort::Title, Sort::Plays, Sort::FirstHeard],
+            Group::Date => &[Sort::Date, Sort::Plays],
+            Group::Artist => &[Sort::Artist, Sort::Plays, Sort::FirstHeard],
+            Group::Album => &[Sort::Artist, Sort::Album, Sort::Plays, Sort::FirstHeard],
+            // how many artists, albums and songs were discovered
+            Group::Discovery => &[Sort::Date, Sort::Artist, Sort::Album, Sort::Title],
+            Group::Player => &[Sort::Player, Sort::Plays],
+            Group::Device => &[Sort::Device, Sort::Plays],
+            Group::Calendar | Group::Wrapped => &[],
+        }
+    }
+
+    // most played first, ties in column order
+    pub fn default_sort(self) -> Vec<SortDirection> {
+        let mut priority = self.columns().iter()
+            .rev()
+            .filter(|sort| **sort != Sort::Plays)
+            .map(|sort| SortDirection(*sort, false))
+            .collect::<Vec<SortDirection>>();
+        if self.columns().contains(&Sort::Plays) {
+            priority.push(SortDirection(Sort::Plays, true));
+        }
+        priority
+    }
+
     pub fn prev(&mut self) {
         *self = match self {
             Group::None => Group::None,
             Group::Date => Group::None,
-            Group::Artist => Group::Date,
-            Group::Album => Group::Artist
+            Group::Calendar => Group::Date,
+            Group::Artist => Group::Calendar,
+            Group::Album => Group::Artist,
+            Group::Player => Group::Album,
+            Group::Device => Group::Player,
+            Group::Wrapped => Group::Device,
+            Group::Discovery => Group::Wrapped,
         }
     }
 
     pub fn next(&mut self) {
         *self = match self {
             Group::None => Group::Date,
-            Group::Date => Group::Artist,
+            Group::Date => Group::Calendar,
+            Group::Calendar => Group::Artist,
             Group::Artist => Group::Album,
-            Group::Album => Group::Album
+            Group::Album => Group::Player,
+            Group::Player => Group::Device,
+            Group::Device => Group::Wrapped,
+            Group::Wrapped => Group::Discovery,
+            Group::Discovery => Group::Discovery,
         };
     }
 }
 
-#[derive(Debug, Default)]
+// what the rows were narrowed down to by pressing Enter on one of them
+#[derive(Debug)]
+enum Drill {
+    Artist(String),
+    // album artist and album
+    Album(String, String),
+    // id and title
+    Song(i64, String),
+}
+
+// enough to put the view back the way it was when drilling back up
+#[derive(Debug)]
+struct DrillLevel {
+    drill: Drill,
+    group: Group,
+    selected: Option<usize>,
+    search: String,
+}
+
+#[derive(Debug)]
+enum PromptKind {
+    Range,
+    Search,
+}
+
+// a line of text being typed into the footer
+#[derive(Debug)]
+struct Prompt {
+    kind: PromptKind,
+    input: String,
+    invalid: bool,
+}
+
+#[derive(Debug)]
 struct TuiState {
     data_vec_none: Vec<SongDataNone>,
     data_vec_date: Vec<SongDataDate>,
+    date_period: Period,
     data_vec_artist: Vec<SongDataArtist>,
     data_vec_album: Vec<SongDataAlbum>,
-    sort_priority: Vec<SortDirection>,
+    data_vec_player: Vec<SongDataSource>,
+    data_vec_device: Vec<SongDataSource>,
+    data_calendar: HashMap<NaiveDate, DateStat>,
+    // plays per period of each artist in the Artist grouping's chart
+    data_trend: Vec<(String, Vec<PeriodStat>)>,
+    trend_shown: bool,
+    trend_period: Period,
+    // artists picked for the chart, the top ones are used when empty
+    compared: Vec<String>,
+    // ignores the range, it always covers wrapped_year
+    data_wrapped: Wrapped,
+    wrapped_year: i32,
+    // what was first played in each week or month of the range
+    data_discovery: Vec<Discoveries>,
+    discovery_period: Period,
+    calendar_cursor: NaiveDate,
+    // colour by listening time instead of plays
+    calendar_time: bool,
+    // only used for noticing new plays, see loader for the rest
+    stats: Stats,
+    loader: Loader,
+    // the row and position to select again once the tables are reloaded, the first row when None
+    reselect: Option<(Option<String>, Option<usize>)>,
+    // parts out of date that aren't shown, they are loaded when their grouping is
+    stale: HashSet<Part>,
+    // why the last load failed, the data from before it is kept until one succeeds
+    load_error: Option<String>,
+    filter: Filter,
+    players: Vec<String>,
+    devices: Vec<String>,
+    range: Range,
+    prompt: Option<Prompt>,
+    search: String,
+    drill: Vec<DrillLevel>,
+    song_detail: Option<SongDetail>,
+    // the song whose detail was last asked for, so it's only asked for once
+    song_detail_requested: Option<i64>,
+    // rows of the current grouping that match search, with the matched positions of each searchable column
+    matches: Vec<(usize, Vec<Vec<usize>>)>,
+    // the sort keys of every grouping, see sort_rows
+    sorts: HashMap<Group, Vec<SortDirection>>,
     group: Group,
     selected_tab: SelectedTab,
     group_state: ListState,
+    range_state: ListState,
     sort_state: ListState,
     table_state: TableState,
     scroll_state: ScrollbarState,
-    weighted: bool,
+    // what songs, artists and albums are ranked by
+    metric: Metric,
+    // last seen PRAGMA data_version, reloads when the daemon writes
+    data_version: i64,
+    now_playing: Option<NowPlaying>,
+    // read once a tick rather than every frame
+    pause_state: PauseState,
+    areas: Areas,
+    // the period last clicked in the Date or artists over time chart
+    chart_point: Option<NaiveDate>,
+    // off so the terminal can select text
+    mouse_captured: bool,
+    theme: Theme,
+    keymap: Keymap,
     exit: bool,
 }
 
-const SELECTED_STYLE: Style = Style::new()
-    .add_modifier(Modifier::REVERSED)
-    .fg(Color::Red);
+// how often the database is checked for new plays
+const TICK_RATE: Duration = Duration::from_secs(1);
+
+// how often the loader is checked for results while it is busy
+const LOADING_POLL_RATE: Duration = Duration::from_millis(50);
 
 impl TuiState {
-    fn new() -> Self {
-        let data_vec_none = TuiState::get_data_vec_none();
-        let data_vec_date = TuiState::get_data_vec_date();
-        let data_vec_artist = TuiState::get_data_vec_artist();
-        let data_vec_album = TuiState::get_data_vec_album();
-
-        let length = data_vec_none.len();
-
-        TuiState {
-            data_vec_none,
-            data_vec_date,
-            data_vec_artist,
-            data_vec_album,
+    fn new(config: &Config) -> Self {
+        let mut tui_state = TuiState {
+            data_vec_none: vec!(),
+            data_vec_date: vec!(),
+            date_period: Period::default(),
+            data_vec_artist: vec!(),
+            data_vec_album: vec!(),
+            data_vec_player: vec!(),
+            data_vec_device: vec!(),
+            data_calendar: HashMap::new(),
+            data_trend: vec!(),
+            trend_shown: false,
+            trend_period: Period::Month,
+            compared: vec!(),
+            data_wrapped: Wrapped::default(),
+            wrapped_year: Local::now().year(),
+            data_discovery: vec!(),
+            discovery_period: Period::Week,
+            calendar_cursor: Local::now().date_naive(),
+            calendar_time: false,
+            stats: Stats::open(get_db_path()).expect("Failed to open database"),
+            loader: Loader::spawn(Stats::open(get_db_path()).expect("Failed to open database")),
+            reselect: None,
+            stale: HashSet::new(),
+            load_error: None,
+            filter: Filter::default(),
+            players: vec!(),
+            devices: vec!(),
+            range: Range::default(),
+            prompt: None,
+            search: String::new(),
+            drill: vec!(),
+            song_detail: None,
+            song_detail_requested: None,
+            matches: vec!(),
             selected_tab: SelectedTab::default(),
-            sort_priority: vec!(SortDirection(Sort::Title, false), SortDirection(Sort::Album, false), SortDirection(Sort::Artist, false), SortDirection(Sort::Plays, true)),
+            sorts: Group::ALL.into_iter().map(|group| (group, group.default_sort())).collect(),
             group: Group::default(),
             sort_state: ListState::default().with_selected(Some(0)),
             group_state: ListState::default().with_selected(Some(0)),
+            range_state: ListState::default().with_selected(Range::ALL.iter().position(|range| *range == Range::default())),
             table_state: TableState::default().with_selected(0),
-            scroll_state: ScrollbarState::new(length),
-            weighted: false,
+            scroll_state: ScrollbarState::default(),
+            metric: Metric::default(),
+            data_version: 0,
+            now_playing: now_playing(),
+            pause_state: pause_state(),
+            areas: Areas::default(),
+            chart_point: None,
+            mouse_captured: true,
+            theme: Theme::load(config),
+            keymap: Keymap::from_config(config),
             exit: false,
-        }
+        };
+
+        tui_state.data_version = tui_state.stats.data_version().unwrap_or_default();
+        tui_state.update_data_all();
+
+        tui_state
     }
 
     pub fn run<B: Backend>(&mut self, terminal: &mut Terminal<B>) -> Result<()> {
-        // let tick_rate = Duration::from_millis(10);
-        // let mut last_tick = Instant::now();
+        let mut last_tick = Instant::now();
 
         while !self.exit {
+            self.receive_data();
             terminal.draw(|frame| self.render_frame(frame))?;
-            // let timeout = tick_rate.saturating_sub(last_tick.elapsed());
+            let timeout = match self.loader.loading() {
+                true => LOADING_POLL_RATE,
+                false => TICK_RATE,
+            }.min(TICK_RATE.saturating_sub(last_tick.elapsed()));
 
             // avoids waiting for event blocking thread
-            // if event::poll(timeout)? {
-            self.handle_events()?;
-            // }
+            if event::poll(timeout)? {
+                self.handle_events()?;
+            }
 
-            // if last_tick.elapsed() >= tick_rate {
-            //     last_tick = Instant::now();
-            // }
+            if last_tick.elapsed() >= TICK_RATE {
+                self.on_tick();
+                last_tick = Instant::now();
+            }
         }
 
         Ok(())
     }
 
-    fn get_data_vec_none() -> Vec<SongDataNone> {
-        Connection::open(get_db_path())
-            .unwrap()
-            .prepare("SELECT artist, album, title, SUM(plays) FROM song_data JOIN song_plays ON song_data.id = song_plays.id GROUP BY song_data.id ORDER BY SUM(plays) DESC")
-            .unwrap()
-            .query_map((), |row| Ok(SongDataNone::new(row.get(0)?, row.get(1)?, row.get(2)?, row.get::<usize, u32>(3)?)))
-            .unwrap()
-            .map(|r| r.unwrap())
-            .collect()
-    }
-
-    fn get_data_vec_date() -> Vec<SongDataDate> {
-        Connection::open(get_db_path())
-            .unwrap()
-            .prepare("SELECT date, SUM(plays) FROM song_plays GROUP BY date ORDER BY SUM(plays) DESC")
-            .unwrap()
-            .query_map((), |row| Ok(SongDataDate::new(row.get(0)?, row.get::<usize, u32>(1)?, 0f32)))
-            .unwrap()
-            .map(|r| r.unwrap())
-            .collect()
-    }
-
-    fn get_data_vec_artist() -> Vec<SongDataArtist> {
-        let mut data: Vec<SongDataArtist> = Connection::open(get_db_path())
-            .unwrap()
-            .prepare("SELECT artist, SUM(plays) FROM song_data JOIN song_plays ON song_data.id = song_plays.id GROUP BY artist ORDER BY SUM(plays) DESC")
-            .unwrap()
-            .query_map((), |row| Ok(SongDataArtist::new(row.get(0)?, row.get::<usize, u32>(1)?, 0f32)))
-            .unwrap()
-            .map(|r| r.unwrap())
-            .collect();
+    fn on_tick(&mut self) {
+        self.now_playing = now_playing();
+        self.pause_state = pause_state();
 
-        let frequency = Self::get_artist_frequency();
+        let Ok(data_version) = self.stats.data_version() else {
+            return;
+        };
 
-        data.iter_mut()
-            .for_each(|x| x.plays_weighted = x.plays as f32 / *frequency.get(&x.artist).expect("") as f32);
+        if data_version != self.data_version {
+            self.data_version = data_version;
+            self.refresh();
+        }
+    }
 
-        let total: f32 = data.iter()
-            .map(|x| x.plays_weighted)
-            .sum();
+    // identifies a row of the current grouping across reloads, which can reorder them
+    fn row_key(&self, i: usize) -> String {
+        match self.group {
+            Group::None => self.data_vec_none[i].id.to_string(),
+            Group::Date => self.data_vec_date[i].date.clone(),
+            Group::Calendar | Group::Wrapped => String::new(),
+            Group::Discovery => self.data_discovery[i].label.clone(),
+            Group::Artist => self.data_vec_artist[i].artist.clone(),
+            Group::Album => format!("{}\0{}", self.data_vec_album[i].artist, self.data_vec_album[i].album),
+            Group::Player => self.data_vec_player[i].source.clone(),
+            Group::Device => self.data_vec_device[i].source.clone(),
+        }
+    }
 
-        data.iter_mut()
-            .for_each(|x| x.plays_weighted /= total);
+    // reloads what is shown, keeping the selected row selected
+    fn refresh(&mut self) {
+        let selected = self.table_state.selected();
+        let key = selected
+            .and_then(|selected| self.matches.get(selected))
+            .map(|(i, _)| self.row_key(*i));
 
-        data
+        self.load_shown();
+        self.reselect = Some((key, selected));
     }
 
-    fn get_data_vec_album() -> Vec<SongDataAlbum> {
-        let mut data: Vec<SongDataAlbum> = Connection::open(get_db_path())
-            .unwrap()
-            .prepare("SELECT album, SUM(plays) FROM song_data JOIN song_plays ON song_data.id = song_plays.id GROUP BY album ORDER BY SUM(plays) DESC")
-            .unwrap()
-            .query_map((), |row| Ok(SongDataAlbum::new(row.get(0)?, row.get::<usize, u32>(1)?, 0f32)))
-            .unwrap()
-            .map(|r| r.unwrap())
-            .collect();
+    fn date_period_next(&mut self) {
+        self.date_period = self.date_period.next();
+        self.refresh();
+    }
 
-        let frequency = Self::get_album_frequency();
+    // weeks or months
+    fn discovery_period_next(&mut self) {
+        self.discovery_period = match self.discovery_period {
+            Period::Week => Period::Month,
+            _ => Period::Week,
+        };
+        self.refresh();
+    }
 
-        data.iter_mut()
-            .for_each(|x| x.plays_weighted = x.plays as f32 / *frequency.get(&x.album).expect("") as f32);
+    fn trend_toggle(&mut self) {
+        self.trend_shown = !self.trend_shown;
+        self.load(&[Part::Trend]);
+    }
 
-        let total: f32 = data.iter()
-            .map(|x| x.plays_weighted)
-            .sum();
+    fn trend_period_next(&mut self) {
+        self.trend_period = self.trend_period.next();
+        self.load(&[Part::Trend]);
+    }
 
-        data.iter_mut()
-            .for_each(|x| x.plays_weighted /= total);
+    fn compare_toggle(&mut self) {
+        let Some(&(i, _)) = self.table_state.selected().and_then(|selected| self.matches.get(selected)) else {
+            return;
+        };
 
-        data
+        let artist = &self.data_vec_artist[i].artist;
+        match self.compared.iter().position(|compared| compared == artist) {
+            Some(position) => {
+                self.compared.remove(position);
+            }
+            None if self.compared.len() < self.theme.series.len() => self.compared.push(artist.clone()),
+            None => {}
+        }
+        self.load(&[Part::Trend]);
     }
 
-    fn get_artist_frequency() -> HashMap<String, u32> {
-        Connection::open(get_db_path())
-            .unwrap()
-            .prepare("SELECT artist, COUNT(artist) FROM song_data GROUP BY artist ORDER BY COUNT(artist) DESC")
-            .unwrap()
-            .query_map((), |row| Ok((row.get(0)?, row.get::<usize, u32>(1)?)))
-            .unwrap()
-            .map(|r| r.unwrap())
-            .collect::<HashMap<String, u32>>()
+    fn wrapped_year(&mut self, years: i32) {
+        self.wrapped_year += years;
+        self.load(&[Part::Wrapped]);
     }
 
-    fn get_album_frequency() -> HashMap<String, u32> {
-        Connection::open(get_db_path())
-            .unwrap()
-            .prepare("SELECT album, COUNT(album) FROM song_data GROUP BY album ORDER BY COUNT(album) DESC")
-            .unwrap()
-            .query_map((), |row| Ok((row.get(0)?, row.get::<usize, u32>(1)?)))
-            .unwrap()
-            .map(|r| r.unwrap())
-            .collect::<HashMap<String, u32>>()
+    fn sort_priority(&self) -> &[SortDirection] {
+        &self.sorts[&self.group]
     }
 
     fn data_sort(&mut self) {
-        for sort_direction in &self.sort_priority {
-            // TODO: change
-            self.data_vec_none.sort_by(|a, b| {
-                let order = match sort_direction.0  {
-                    Sort::Artist => a.artist().cmp(b.artist()),
-                    Sort::Album => a.album().cmp(b.album()),
-                    Sort::Title => a.title().cmp(b.title()),
-                    Sort::Plays => a.plays().cmp(&b.plays()),
-                };
-                if sort_direction.1 { order.reverse() } else { order }
+        sort_rows(&mut self.data_vec_none, &self.sorts[&Group::None], self.metric);
+        sort_rows(&mut self.data_vec_date, &self.sorts[&Group::Date], self.metric);
+        sort_rows(&mut self.data_vec_artist, &self.sorts[&Group::Artist], self.metric);
+        sort_rows(&mut self.data_vec_album, &self.sorts[&Group::Album], self.metric);
+        sort_rows(&mut self.data_vec_player, &self.sorts[&Group::Player], self.metric);
+        sort_rows(&mut self.data_vec_device, &self.sorts[&Group::Device], self.metric);
+        sort_rows(&mut self.data_discovery, &self.sorts[&Group::Discovery], self.metric);
+        self.update_matches();
+    }
+
+    // reorders the rows by it when it is the most significant key
+    fn metric_next(&mut self) {
+        self.metric = self.metric.next();
+        self.data_sort();
+    }
+
+    // the Date and source tables and the trend chart only count plays
+    fn metric_applies(&self) -> bool {
+        match self.group {
+            Group::None | Group::Album => true,
+            Group::Artist => !self.trend_shown,
+            _ => false,
+        }
+    }
+
+    // makes sort the most significant key, or flips its direction when it already is
+    fn sort_by(&mut self, sort: Sort) {
+        if let Some(priority) = self.sorts.get_mut(&self.group) {
+            promote(priority, sort);
+            self.data_sort();
+        }
+    }
+
+    // the column after the most significant one
+    fn sort_column_next(&mut self) {
+        let columns = self.group.columns();
+        let Some(primary) = self.sort_priority().last() else {
+            return;
+        };
+
+        let i = columns.iter().position(|sort| *sort == primary.0).unwrap_or_default();
+        self.sort_by(columns[(i + 1) % columns.len()]);
+    }
+
+    fn sort_reverse_primary(&mut self) {
+        if let Some(primary) = self.sort_priority().last() {
+            self.sort_by(primary.0);
+        }
+    }
+
+    // marks the most significant key with its direction
+    fn table_header(&self, labels: &[&str]) -> Row<'static> {
+        let primary = self.sort_priority().last();
+
+        labels.iter()
+            .zip(self.group.columns())
+            .map(|(label, sort)| match primary {
+                Some(SortDirection(primary, descending)) if primary == sort => format!("[{} {}]", label, if *descending { "↓" } else { "↑" }),
+                _ => format!("[{}]", label),
             })
+            .map(Cell::from)
+            .collect::<Row>()
+            .style(self.theme.accent)
+            .height(2)
+    }
+
+    // the filter applies to every grouping so all of them are out of date, the first row is selected once they're loaded
+    fn update_data_all(&mut self) {
+        self.reselect = None;
+        self.load_shown();
+    }
+
+    // what the current grouping shows
+    fn shown_parts(&self) -> Vec<Part> {
+        match self.group {
+            Group::Calendar => vec!(Part::Calendar),
+            Group::Wrapped => vec!(Part::Wrapped),
+            Group::Artist if self.trend_shown => vec!(Part::Tables, Part::Trend),
+            _ => vec!(Part::Tables),
         }
     }
 
-    fn update_data(&mut self) {
-        self.table_state.select_first();
+    // loads what is shown now and leaves the rest for when it is
+    fn load_shown(&mut self) {
+        self.stale.extend(Part::ALL);
+        self.load(&self.shown_parts());
+    }
+
+    // after switching groupings, the tables start at the first row like they would have anyway
+    fn load_stale(&mut self) {
+        let parts: Vec<Part> = self.shown_parts().into_iter().filter(|part| self.stale.contains(part)).collect();
+        if parts.contains(&Part::Tables) {
+            self.reselect = None;
+        }
+        if !parts.is_empty() {
+            self.load(&parts);
+        }
+    }
+
+    fn load(&mut self, parts: &[Part]) {
+        for part in parts {
+            self.stale.remove(part);
+        }
+
+        let query = Query {
+            filter: self.filter.clone(),
+            date_period: self.date_period,
+            calendar_year: self.calendar_cursor.year(),
+            trend_shown: self.trend_shown,
+            trend_period: self.trend_period,
+            compared: self.compared.clone(),
+            wrapped_year: self.wrapped_year,
+            discovery_period: self.discovery_period,
+            song: self.selected_song().map(|song| song.id),
+        };
+        self.loader.request(query, parts);
+    }
+
+    // takes whatever the loader has finished since the last frame
+    fn receive_data(&mut self) {
+        while let Some(data) = self.loader.try_recv() {
+            match data {
+                Ok(data) => {
+                    self.load_error = None;
+                    self.apply_data(data);
+                }
+                Err(err) => self.load_error = Some(err.to_string()),
+            }
+        }
+    }
+
+    fn apply_data(&mut self, data: Data) {
+        if let Some(tables) = data.tables {
+            self.data_vec_none = tables.none;
+            self.data_vec_date = tables.date;
+            self.data_vec_artist = tables.artist;
+            self.data_vec_album = tables.album;
+            self.data_vec_player = tables.player;
+            self.data_vec_device = tables.device;
+            self.data_discovery = tables.discovery;
+
+            // the first load is unfiltered
+            if self.players.is_empty() && self.devices.is_empty() {
+                self.players = self.data_vec_player.iter().map(|data| data.source.clone()).collect();
+                self.devices = self.data_vec_device.iter().map(|data| data.source.clone()).collect();
+            }
+
+            // the song's plays may have changed too
+            self.song_detail = None;
+            self.song_detail_requested = None;
+            self.data_sort();
+            self.scroll_reset();
+
+            let selected = match self.reselect.take() {
+                Some((key, selected)) => key
+                    .and_then(|key| self.matches.iter().position(|(i, _)| self.row_key(*i) == key))
+                    .or(selected.map(|selected| selected.min(self.matches.len().saturating_sub(1)))),
+                None => Some(0),
+            };
+            self.table_state.select(selected);
+            self.scroll_state = self.scroll_state.position(selected.unwrap_or_default());
+        }
+        if let Some(calendar) = data.calendar {
+            self.data_calendar = calendar;
+        }
+        if let Some(trend) = data.trend {
+            self.data_trend = trend;
+        }
+        if let Some(wrapped) = data.wrapped {
+            self.data_wrapped = wrapped;
+        }
+        if let Some(song_detail) = data.song_detail {
+            self.song_detail = Some(song_detail);
+        }
+    }
+
+    fn player_filter_next(&mut self) {
+        self.filter.player = TuiState::cycle_filter(&self.players, &self.filter.player);
+        self.update_data_all();
+    }
+
+    fn device_filter_next(&mut self) {
+        self.filter.device = TuiState::cycle_filter(&self.devices, &self.filter.device);
+        self.update_data_all();
+    }
+
+    // the columns search looks through for every row of the current grouping
+    fn search_fields(&self) -> Vec<Vec<&str>> {
         match self.group {
-            Group::None => self.data_vec_none = TuiState::get_data_vec_none(),
-            Group::Date => self.data_vec_date = TuiState::get_data_vec_date(),
-            Group::Artist => self.data_vec_artist = TuiState::get_data_vec_artist(),
-            Group::Album => self.data_vec_album = TuiState::get_data_vec_album(),
+            Group::None => self.data_vec_none.iter().map(|data| vec!(data.artist(), data.album(), data.title())).collect(),
+            Group::Date => self.data_vec_date.iter().map(|data| vec!(data.date.as_str())).collect(),
+            Group::Calendar | Group::Wrapped => vec!(),
+            Group::Discovery => self.data_discovery.iter().map(|data| vec!(data.label.as_str())).collect(),
+            Group::Artist => self.data_vec_artist.iter().map(|data| vec!(data.artist.as_str())).collect(),
+            Group::Album => self.data_vec_album.iter().map(|data| vec!(data.artist.as_str(), data.album.as_str())).collect(),
+            Group::Player => self.data_vec_player.iter().map(|data| vec!(source_name(&data.source))).collect(),
+            Group::Device => self.data_vec_device.iter().map(|data| vec!(source_name(&data.source))).collect(),
+        }
+    }
+
+    fn update_matches(&mut self) {
+        let matches = self.search_fields()
+            .iter()
+            .enumerate()
+            .filter_map(|(i, fields)| fuzzy_match(&self.search, fields).map(|highlights| (i, highlights)))
+            .collect();
+        self.matches = matches;
+    }
+
+    fn search_start(&mut self) {
+        self.prompt = Some(Prompt {
+            kind: PromptKind::Search,
+            invalid: false,
+            input: self.search.clone(),
+        });
+    }
+
+    fn search_changed(&mut self, search: String) {
+        self.search = search;
+        self.update_matches();
+        self.table_state.select_first();
+        self.scroll_reset();
+    }
+
+    // wraps around at either end
+    fn search_jump(&mut self, forward: bool) {
+        if self.matches.is_empty() {
+            return;
+        }
+
+        let selected = self.table_state.selected().unwrap_or_default().min(self.matches.len() - 1);
+        let next = match forward {
+            true => (selected + 1) % self.matches.len(),
+            false => (selected + self.matches.len() - 1) % self.matches.len(),
+        };
+        self.table_state.select(Some(next));
+        self.scroll_state = self.scroll_state.position(next);
+    }
+
+    fn drill_down(&mut self) {
+        let Some(&(i, _)) = self.table_state.selected().and_then(|selected| self.matches.get(selected)) else {
+            return;
+        };
+
+        let (drill, group) = match self.group {
+            Group::Artist => (Drill::Artist(self.data_vec_artist[i].artist.clone()), Group::Album),
+            Group::Album => {
+                let data = &self.data_vec_album[i];
+                (Drill::Album(data.artist.clone(), data.album.clone()), Group::None)
+            }
+            Group::None => {
+                let data = &self.data_vec_none[i];
+                (Drill::Song(data.id, data.title.clone()), Group::Date)
+            }
+            Group::Date | Group::Calendar | Group::Player | Group::Device | Group::Wrapped | Group::Discovery => return,
+        };
+
+        self.drill.push(DrillLevel {
+            drill,
+            group: self.group,
+            selected: self.table_state.selected(),
+            search: std::mem::take(&mut self.search),
+        });
+        self.set_group(group);
+        self.drill_apply();
+    }
+
+    fn drill_up(&mut self) {
+        let Some(level) = self.drill.pop() else {
+            return;
         };
+
+        self.search = level.search;
+        self.set_group(level.group);
+        self.drill_apply();
+        self.reselect = Some((None, level.selected));
+    }
+
+    // every level narrows the filter further
+    fn drill_apply(&mut self) {
+        self.filter.artist = None;
+        self.filter.album = None;
+        self.filter.song = None;
+
+        for level in &self.drill {
+            match &level.drill {
+                Drill::Artist(artist) => self.filter.artist = Some(artist.clone()),
+                Drill::Album(artist, album) => self.filter.album = Some((artist.clone(), album.clone())),
+                Drill::Song(id, _) => self.filter.song = Some(*id),
+            }
+        }
+
+        // the new grouping's rows stay as they were until it is reloaded
+        self.update_matches();
         self.scroll_reset();
+        self.update_data_all();
+    }
+
+    fn breadcrumb(&self) -> String {
+        self.drill.iter()
+            .map(|level| match &level.drill {
+                Drill::Artist(artist) => artist.as_str(),
+                Drill::Album(_, album) => album.as_str(),
+                Drill::Song(_, title) => title.as_str(),
+            })
+            .collect::<Vec<&str>>()
+            .join(" > ")
+    }
+
+    fn calendar_move(&mut self, days: i64) {
+        let year = self.calendar_cursor.year();
+        self.calendar_cursor = (self.calendar_cursor + TimeDelta::days(days)).clamp(first_day(year), last_day(year));
+    }
+
+    fn calendar_year(&mut self, years: i32) {
+        self.calendar_cursor = same_day_in(self.calendar_cursor, self.calendar_cursor.year() + years);
+        self.load(&[Part::Calendar]);
+    }
+
+    fn set_range(&mut self, range: Range) {
+        if let Some((from, to)) = range.bounds(Local::now().date_naive()) {
+            self.range = range;
+            self.filter.from = from;
+            self.filter.to = to;
+            self.update_data_all();
+        }
+    }
+
+    fn range_prev(&mut self) {
+        self.range_state.select_previous();
+        self.range_select();
+    }
+
+    fn range_next(&mut self) {
+        self.range_state.select_next();
+        self.range_select();
+    }
+
+    fn range_cursor(&self) -> Range {
+        Range::ALL[self.range_state.selected().unwrap_or_default().min(Range::ALL.len() - 1)]
+    }
+
+    // presets apply straight away, a custom range has to be typed in first
+    fn range_select(&mut self) {
+        let range = self.range_cursor();
+        if range != Range::Custom {
+            self.set_range(range);
+        }
+    }
+
+    fn range_custom(&mut self) {
+        self.prompt = Some(Prompt {
+            kind: PromptKind::Range,
+            invalid: false,
+            input: match self.range {
+                Range::AllTime => String::new(),
+                _ => format_bounds(&(self.filter.from, self.filter.to)),
+            },
+        });
+    }
+
+    fn prompt_submit(&mut self) {
+        let Some(prompt) = self.prompt.take() else {
+            return;
+        };
+
+        match prompt.kind {
+            PromptKind::Range => match parse_bounds(&prompt.input) {
+                Some((from, to)) => {
+                    self.range = Range::Custom;
+                    self.filter.from = from;
+                    self.filter.to = to;
+                    self.update_data_all();
+                }
+                // keep it open so the typo can be fixed
+                None => self.prompt = Some(Prompt { invalid: true, ..prompt }),
+            },
+            PromptKind::Search => {}
+        }
+    }
+
+    // all -> first -> ... -> last -> all
+    fn cycle_filter(options: &[String], current: &Option<String>) -> Option<String> {
+        match current {
+            None => options.first().cloned(),
+            Some(current) => options.iter()
+                .skip_while(|option| *option != current)
+                .nth(1)
+                .cloned(),
+        }
     }
 
     fn render_frame(&mut self, frame: &mut Frame) {
-        let [main_area, footer_area] = Layout::vertical([
+        // whatever isn't drawn this frame can't be clicked
+        self.areas = Areas::default();
+
+        let [main_area, now_playing_area, footer_area] = Layout::vertical([
             Constraint::Fill(1),
+            Constraint::Length(if self.now_playing.is_some() { 3 } else { 0 }),
             Constraint::Length(3),
         ]).areas(frame.area());
 
+        self.render_now_playing(frame, now_playing_area);
+
         let [sidebar_area, table_area] = Layout::horizontal([
-            Constraint::Length(14),
+            Constraint::Length(22),
             Constraint::Fill(1)
         ]).areas(main_area);
 
@@ -386,20 +1032,57 @@ impl TuiState {
         ]).areas(table_area);
 
         match self.group {
-            // Group::None => {}
+            Group::None => {
+                let [table_area, detail_area] = Layout::horizontal([
+                    Constraint::Fill(2),
+                    Constraint::Fill(1)
+                ]).areas(table_area);
+
+                self.render_sidebar(frame, sidebar_area);
+                self.render_table(frame, table_area);
+                self.render_song_detail(frame, detail_area);
+                self.render_footer(frame, footer_area);
+            }
             Group::Date => {
                 self.render_sidebar(frame, sidebar_area);
                 self.render_table(frame, table_area_small);
                 self.render_line_chart_date(frame, chart_area);
                 self.render_footer(frame, footer_area);
             }
-            // Group::Artist => {
-            //     self.render_sidebar(frame, sidebar_area);
-            //     self.render_table(frame, table_area_small);
-            //     self.render_bar_chart(frame, chart_area);
-            //     self.render_footer(frame, footer_area);
-            // }
-            // Group::Album => {}
+            Group::Calendar => {
+                self.render_sidebar(frame, sidebar_area);
+                self.render_calendar(frame, table_area);
+                self.render_footer(frame, footer_area);
+            }
+            Group::Wrapped => {
+                self.render_sidebar(frame, sidebar_area);
+                self.render_wrapped(frame, table_area);
+                self.render_footer(frame, footer_area);
+            }
+            Group::Discovery => {
+                let [chart_area, discovered_area] = Layout::vertical([
+                    Constraint::Fill(1),
+                    Constraint::Fill(1)
+                ]).areas(chart_area);
+
+                self.render_sidebar(frame, sidebar_area);
+                self.render_table(frame, table_area_small);
+                self.render_discovery_chart(frame, chart_area);
+                self.render_discovered(frame, discovered_area);
+                self.render_footer(frame, footer_area);
+            }
+            Group::Artist if self.trend_shown => {
+                self.render_sidebar(frame, sidebar_area);
+                self.render_table(frame, table_area_small);
+                self.render_trend_chart(frame, chart_area);
+                self.render_footer(frame, footer_area);
+            }
+            Group::Artist | Group::Album => {
+                self.render_sidebar(frame, sidebar_area);
+                self.render_table(frame, table_area_small);
+                self.render_bar_chart(frame, chart_area);
+                self.render_footer(frame, footer_area);
+            }
             _ => {
                 self.render_sidebar(frame, sidebar_area);
                 self.render_table(frame, table_area);
@@ -410,13 +1093,14 @@ impl TuiState {
     }
 
     fn render_sidebar(&mut self, frame: &mut Frame, area: Rect) {
-        let [group_area, sort_area] = Layout::vertical([
-            Constraint::Fill(1),
+        let [group_area, range_area, sort_area] = Layout::vertical([
+            Constraint::Length(13),
+            Constraint::Length(11),
             Constraint::Fill(1)
         ]).areas(area);
 
         let grouping_border_style = match self.selected_tab {
-            SelectedTab::Group => Style::from(Color::Red),
+            SelectedTab::Group => self.theme.accent,
             _ => Style::default(),
         };
 
@@ -424,16 +1108,35 @@ impl TuiState {
             .title(Title::from(" Grouping ").alignment(Alignment::Center))
             .border_style(grouping_border_style)
             .padding(Padding::uniform(1));
+        self.areas.group = group_block.inner(group_area);
 
-        let group_list = List::new(["None", "Date", "Artist", "Album"])
+        let group_list = List::new(["None", "Date", "Calendar", "Artist", "Album", "Player", "Device", "Wrapped", "Discovery"])
             .block(group_block)
             .highlight_symbol("> ")
-            .highlight_style(SELECTED_STYLE);
+            .highlight_style(self.theme.selected);
 
         frame.render_stateful_widget(group_list, group_area, &mut self.group_state);
 
+        let range_border_style = match self.selected_tab {
+            SelectedTab::Range => self.theme.accent,
+            _ => Style::default(),
+        };
+
+        let range_block = Block::bordered()
+            .title(Title::from(" Range ").alignment(Alignment::Center))
+            .border_style(range_border_style)
+            .padding(Padding::uniform(1));
+        self.areas.range = range_block.inner(range_area);
+
+        let range_list = List::new(Range::ALL.map(|range| range.to_string()))
+            .block(range_block)
+            .highlight_symbol("> ")
+            .highlight_style(self.theme.selected);
+
+        frame.render_stateful_widget(range_list, range_area, &mut self.range_state);
+
         let sort_border_style = match self.selected_tab {
-            SelectedTab::Sort => Style::from(Color::Red),
+            SelectedTab::Sort => self.theme.accent,
             _ => Style::default(),
         };
 
@@ -441,20 +1144,22 @@ impl TuiState {
             .title(Title::from(" Sorting ").alignment(Alignment::Center))
             .border_style(sort_border_style)
             .padding(Padding::uniform(1));
+        self.areas.sort = sort_block.inner(sort_area);
 
-        let sort_vector = self.sort_priority.iter()
+        let sort_vector = self.sort_priority().iter()
             .rev()
             .enumerate()
             .map(|(i, sort)| {
                 let mut prefix = format!("{}. ", i+1).to_owned();
                 prefix.push_str(&sort.0.to_string());
+                prefix.push_str(if sort.1 { " ↓" } else { " ↑" });
                 prefix
             })
             .collect::<Vec<String>>();
 
         let sort_list = List::new(sort_vector)
             .block(sort_block)
-            .highlight_style(SELECTED_STYLE);
+            .highlight_style(self.theme.selected);
 
         frame.render_stateful_widget(sort_list, sort_area, &mut self.sort_state);
     }
@@ -462,22 +1167,37 @@ impl TuiState {
     // https://github.com/ratatui/ratatui/issues/1004
     fn render_table(&mut self, frame: &mut Frame, area: Rect) {
         let border_style = match self.selected_tab {
-            SelectedTab::Table => Style::from(Color::Red),
+            SelectedTab::Table => self.theme.accent,
             _ => Style::default(),
         };
 
+        let title = match self.drill.is_empty() {
+            true => " Song Table ".to_string(),
+            false => format!(" Song Table: {} ", self.breadcrumb()),
+        };
+
         let block = Block::bordered()
-            .title(Line::raw(" Song Table ").centered())
+            .title(Line::raw(title).centered())
             .border_style(border_style)
             .padding(Padding::new(1, 3, 0, 0));
+        self.areas.table = block.inner(area);
+
+        // only the rows in view are built, drawn as if they were the whole table
+        let height = self.areas.table.height.saturating_sub(HEADER_HEIGHT) as usize;
+        let window = visible_rows(self.table_state.offset(), self.table_state.selected(), self.matches.len(), height);
+        *self.table_state.offset_mut() = window.start;
+        let mut state = TableState::default().with_selected(self.table_state.selected().map(|selected| selected.saturating_sub(window.start)));
 
         match self.group {
             Group::None => {
-                let rows: Vec<Row> = self.data_vec_none.iter()
-                    .map(|data| {
+                let rows: Vec<Row> = self.matches[window.clone()].iter()
+                    .map(|(i, highlights)| {
+                        let data = &self.data_vec_none[*i];
                         data.ref_array()
                             .into_iter()
-                            .map(|string| Cell::from(Text::from(string)))
+                            .enumerate()
+                            .map(|(column, string)| Cell::from(Text::from(highlight(string, highlights.get(column).map_or(&[], Vec::as_slice)))))
+                            .chain([Cell::from(self.metric.format(data.measures())), Cell::from(data.first_heard.as_str())])
                             .collect::<Row>()
                     })
                     .collect();
@@ -486,28 +1206,26 @@ impl TuiState {
                     Constraint::Fill(1),
                     Constraint::Fill(3),
                     Constraint::Fill(3),
-                    Constraint::Max(10)
+                    Constraint::Max(10),
+                    Constraint::Length(10)
                 ];
+                self.areas.columns = table_columns(self.areas.table, &widths);
 
-                let header = ["[Artist]", "[Album]", "[Title]", "[Plays]"]
-                    .into_iter()
-                    .map(Cell::from)
-                    .collect::<Row>()
-                    .red()
-                    .height(2);
+                let header = self.table_header(&["Artist", "Album", "Title", self.metric.heading(), "First"]);
 
                 let table = Table::new(rows, widths)
                     .block(block)
                     .header(header)
-                    .highlight_style(SELECTED_STYLE);
+                    .highlight_style(self.theme.selected);
 
-                frame.render_stateful_widget(table, area, &mut self.table_state);
+                frame.render_stateful_widget(table, area, &mut state);
             },
             Group::Date => {
-                let rows: Vec<Row> = self.data_vec_date.iter()
-                    .map(|data| {
+                let rows: Vec<Row> = self.matches[window.clone()].iter()
+                    .map(|(i, highlights)| {
+                        let data = &self.data_vec_date[*i];
                         Row::new(vec!(
-                            Cell::new(data.date.clone()),
+                            Cell::new(highlight(&data.date, &highlights[0])),
                             Cell::new(data.plays.to_string()))
                         )
                     })
@@ -517,78 +1235,139 @@ impl TuiState {
                     Constraint::Fill(1),
                     Constraint::Max(10)
                 ];
+                self.areas.columns = table_columns(self.areas.table, &widths);
 
-                let header = ["[Date]", "[Plays]"]
-                    .into_iter()
-                    .map(Cell::from)
-                    .collect::<Row>()
-                    .red()
-                    .height(2);
+                let header = self.table_header(&[self.date_period.name(), "Plays"]);
 
                 let table = Table::new(rows, widths)
-                    .block(block)
+                    .block(block.title_bottom(self.title_hints(&[(&[Action::Period], "Day/Week/Month/Year")])))
                     .header(header)
-                    .highlight_style(SELECTED_STYLE);
+                    .highlight_style(self.theme.selected);
 
-                frame.render_stateful_widget(table, area, &mut self.table_state);
+                frame.render_stateful_widget(table, area, &mut state);
             },
             Group::Artist => {
-                let rows: Vec<Row> = self.data_vec_artist.iter()
-                    .map(|data| {
+                let rows: Vec<Row> = self.matches[window.clone()].iter()
+                    .map(|(i, highlights)| {
+                        let data = &self.data_vec_artist[*i];
+                        let mut artist = highlight(&data.artist, &highlights[0]);
+                        if let Some(color) = self.compared.iter().position(|compared| *compared == data.artist) {
+                            artist.spans.insert(0, Span::styled("● ", Style::from(self.theme.series[color])));
+                        }
                         Row::new(vec!(
-                            Cell::new(data.artist.clone()),
-                            Cell::new((if self.weighted { format!("{:.4}%", data.plays_weighted * 100f32) } else { data.plays.to_string() }))
+                            Cell::new(artist),
+                            Cell::new(self.metric.format(&data.measures)),
+                            Cell::new(data.first_heard.as_str())
                         ))
                     })
                     .collect();
 
                 let widths = [
                     Constraint::Fill(1),
-                    Constraint::Max(10)
+                    Constraint::Max(10),
+                    Constraint::Length(10)
                 ];
+                self.areas.columns = table_columns(self.areas.table, &widths);
 
-                let header = ["[Artist]", "[Plays]"]
-                    .into_iter()
-                    .map(Cell::from)
-                    .collect::<Row>()
-                    .red()
-                    .height(2);
+                let header = self.table_header(&["Artist", self.metric.heading(), "First"]);
 
                 let table = Table::new(rows, widths)
                     .block(block)
                     .header(header)
-                    .highlight_style(SELECTED_STYLE);
+                    .highlight_style(self.theme.selected);
 
-                frame.render_stateful_widget(table, area, &mut self.table_state);
+                frame.render_stateful_widget(table, area, &mut state);
             },
             Group::Album => {
-                let rows: Vec<Row> = self.data_vec_album.iter()
-                    .map(|data| {
+                let rows: Vec<Row> = self.matches[window.clone()].iter()
+                    .map(|(i, highlights)| {
+                        let data = &self.data_vec_album[*i];
                         Row::new(vec!(
-                            Cell::new(data.album.clone()),
-                            Cell::new((if self.weighted { format!("{:.4}%", data.plays_weighted * 100f32) } else { data.plays.to_string() }))
+                            Cell::new(highlight(&data.artist, &highlights[0])),
+                            Cell::new(highlight(&data.album, &highlights[1])),
+                            Cell::new(self.metric.format(&data.measures)),
+                            Cell::new(data.first_heard.as_str())
                         ))
                     })
                     .collect();
 
                 let widths = [
                     Constraint::Fill(1),
-                    Constraint::Max(10)
+                    Constraint::Fill(2),
+                    Constraint::Max(10),
+                    Constraint::Length(10)
                 ];
+                self.areas.columns = table_columns(self.areas.table, &widths);
 
-                let header = ["[Album]", "[Plays]"]
-                    .into_iter()
-                    .map(Cell::from)
-                    .collect::<Row>()
-                    .red()
-                    .height(2);
+                let header = self.table_header(&["Artist", "Album", self.metric.heading(), "First"]);
 
                 let table = Table::new(rows, widths)
                     .block(block)
                     .header(header)
-                    .highlight_style(SELECTED_STYLE);
+                    .highlight_style(self.theme.selected);
 
-                frame.render_stateful_widget(table, area, &mut self.table_state);
+                frame.render_stateful_widget(table, area, &mut state);
+            }
+            Group::Discovery => {
+                let rows: Vec<Row> = self.matches[window.clone()].iter()
+                    .map(|(i, highlights)| {
+                        let data = &self.data_discovery[*i];
+                        Row::new(vec!(
+                            Cell::new(highlight(&data.label, &highlights[0])),
+                            Cell::new(data.artists.len().to_string()),
+                            Cell::new(data.albums.len().to_string()),
+                            Cell::new(data.songs.len().to_string())
+                        ))
+                    })
+                    .collect();
+
+                let widths = [
+                    Constraint::Fill(1),
+                    Constraint::Max(11),
+                    Constraint::Max(10),
+                    Constraint::Max(10)
+                ];
+                self.areas.columns = table_columns(self.areas.table, &widths);
+
+                let header = self.table_header(&[self.discovery_period.name(), "Artists", "Albums", "Songs"]);
+
+                let table = Table::new(rows, widths)
+                    .block(block.title_bottom(self.title_hints(&[(&[Action::Period], "Week/Month")])))
+                    .header(header)
+                    .highlight_style(self.theme.selected);
+
+                frame.render_stateful_widget(table, area, &mut state);
+            }
+            Group::Calendar | Group::Wrapped => return,
+            Group::Player | Group::Device => {
+                let (data_vec, column) = match self.group {
+                    Group::Player => (&self.data_vec_player, "Player"),
+                    _ => (&self.data_vec_device, "Device"),
+                };
+
+                let rows: Vec<Row> = self.matches[window.clone()].iter()
+                    .map(|(i, highlights)| {
+                        Row::new(vec!(
+                            Cell::new(highlight(source_name(&data_vec[*i].source), &highlights[0])),
+                            Cell::new(data_vec[*i].plays.to_string())
+                        ))
+                    })
+                    .collect();
+
+                let widths = [
+                    Constraint::Fill(1),
+                    Constraint::Max(10)
+                ];
+                self.areas.columns = table_columns(self.areas.table, &widths);
+
+                let header = self.table_header(&[column, "Plays"]);
+
+                let table = Table::new(rows, widths)
+                    .block(block)
+                    .header(header)
+                    .highlight_style(self.theme.selected);
+
+                frame.render_stateful_widget(table, area, &mut state);
             }
         };
 
@@ -597,7 +1376,7 @@ impl TuiState {
             .label("")
             .ratio(1.);
 
-        let mut small_area = area.clone();
+        let mut small_area = area;
         small_area.height = 1;
         small_area.width -= 5;
         small_area.x += 1;
@@ -609,7 +1388,7 @@ impl TuiState {
             .orientation(ScrollbarOrientation::VerticalRight)
             .begin_symbol(Some("↑"))
             .thumb_symbol("█")
-            .thumb_style(Color::Red)
+            .thumb_style(self.theme.accent)
             .track_symbol(Some("│"))
             .end_symbol(Some("↓"));
 
@@ -620,19 +1399,19 @@ impl TuiState {
         );
     }
 
-    fn render_line_chart_date(&self, frame: &mut Frame, area: Rect) {
-        let mut cloned = self.data_vec_date.clone();
-        cloned.sort_by(|a, b| a.date.cmp(&b.date));
+    fn render_line_chart_date(&mut self, frame: &mut Frame, area: Rect) {
+        if self.data_vec_date.is_empty() {
+            return;
+        }
 
-        let min_date = cloned[0].date.clone();
-        let max_date = cloned[cloned.len() - 1].date.clone();
+        let mut sorted = self.data_vec_date.iter().collect::<Vec<&SongDataDate>>();
+        sorted.sort_by_key(|data| data.start);
 
-        let data = cloned.iter()
-            .map(|song| {
-                let mut s =  song.date.clone();
-                s.push_str("T00:00:00Z");
-                (s.parse::<DateTime<Utc>>().unwrap().timestamp() as f64, song.plays as f64)
-            })
+        let min_date = sorted[0].date.clone();
+        let max_date = sorted[sorted.len() - 1].date.clone();
+
+        let data = sorted.iter()
+            .map(|song| (song.start.num_days_from_ce() as f64, song.plays as f64))
             .collect::<Vec<(f64, f64)>>();
 
         let min_time = data[0].0;
@@ -640,6 +1419,20 @@ impl TuiState {
 
         let max_plays = data.iter().max_by(|a, b| a.1.partial_cmp(&b.1).unwrap()).unwrap().1;
 
+        let block = Block::bordered()
+            .title(
+                Title::default()
+                    .content(" Line chart ")
+                    .alignment(Alignment::Center),
+            )
+            .padding(Padding::uniform(1));
+
+        let block = match sorted.iter().find(|data| Some(data.start) == self.chart_point) {
+            Some(point) => block.title_bottom(Line::raw(format!(" {}: {} plays ", point.date, point.plays)).centered()),
+            None => block,
+        };
+        self.areas.chart = graph_area(block.inner(area), &["0", &max_plays.to_string()], &min_date);
+
         let dataset = vec![
             Dataset::default()
                 .marker(symbols::Marker::Braille)
@@ -649,18 +1442,10 @@ impl TuiState {
         ];
 
         let chart = Chart::new(dataset)
-            .block(
-                Block::bordered()
-                    .title(
-                        Title::default()
-                            .content(" Line chart ")
-                            .alignment(Alignment::Center),
-                    )
-                    .padding(Padding::uniform(1))
-            )
+            .block(block)
             .x_axis(
                 Axis::default()
-                    .title("Date")
+                    .title(self.date_period.name())
                     .style(Style::default())
                     .bounds([min_time, max_time])
                     .labels([min_date, max_date]),
@@ -678,89 +1463,734 @@ impl TuiState {
         frame.render_widget(chart, area);
     }
 
-    fn render_bar_chart(&self, frame: &mut Frame, area: Rect) {
-        // let block = Block::bordered()
-        //     .title(
-        //         Title::default()
-        //             .content(" Bar chart ")
-        //             .alignment(Alignment::Center),
-        //     )
-        //     .padding(Padding::uniform(1));
+    fn render_trend_chart(&mut self, frame: &mut Frame, area: Rect) {
+        let block = Block::bordered()
+            .title(Title::default().content(" Artists over time ").alignment(Alignment::Center))
+            .title_bottom(self.title_hints(&[
+                (&[Action::Chart], "Bars"),
+                (&[Action::Toggle], "Compare"),
+                (&[Action::Period], "Day/Week/Month/Year"),
+            ]))
+            .padding(Padding::uniform(1));
+
+        let series = self.data_trend.iter()
+            .map(|(artist, periods)| {
+                let data = periods.iter()
+                    .map(|period| (period.start.num_days_from_ce() as f64, period.plays as f64))
+                    .collect::<Vec<(f64, f64)>>();
+                (artist.as_str(), data)
+            })
+            .collect::<Vec<(&str, Vec<(f64, f64)>)>>();
+
+        let Some((_, periods)) = self.data_trend.first().filter(|(_, periods)| !periods.is_empty()) else {
+            frame.render_widget(block, area);
+            return;
+        };
 
-        let data = self.data_vec_artist.iter()
-            .map(|song| (song.artist.as_str(), song.plays as u64))
-            .collect::<Vec<(&str, u64)>>();
+        let min_time = periods[0].start.num_days_from_ce() as f64;
+        let max_time = periods[periods.len() - 1].start.num_days_from_ce() as f64;
+        let max_plays = series.iter()
+            .flat_map(|(_, data)| data.iter().map(|(_, plays)| *plays))
+            .fold(1.0, f64::max);
+
+        // every series covers the same periods, see loader::trend
+        let block = match periods.iter().position(|period| Some(period.start) == self.chart_point) {
+            Some(point) => {
+                let values = self.data_trend.iter()
+                    .map(|(artist, periods)| format!("{} {}", artist, periods[point].plays))
+                    .collect::<Vec<String>>();
+                block.title_bottom(Line::raw(format!(" {}: {} ", periods[point].label, values.join(", "))).left_aligned())
+            }
+            None => block,
+        };
+        self.areas.chart = graph_area(block.inner(area), &["0", &max_plays.to_string()], &periods[0].label);
+
+        let datasets = series.iter()
+            .zip(self.theme.series)
+            .map(|((artist, data), color)| {
+                Dataset::default()
+                    .name(artist.to_string())
+                    .marker(symbols::Marker::Braille)
+                    .style(Style::from(color))
+                    .graph_type(GraphType::Line)
+                    .data(data)
+            })
+            .collect::<Vec<Dataset>>();
+
+        let chart = Chart::new(datasets)
+            .block(block)
+            .x_axis(
+                Axis::default()
+                    .title(self.trend_period.name())
+                    .bounds([min_time, max_time.max(min_time + 1.0)])
+                    .labels([periods[0].label.clone(), periods[periods.len() - 1].label.clone()]),
+            )
+            .y_axis(
+                Axis::default()
+                    .title("Plays")
+                    .bounds([0.0, max_plays])
+                    .labels(["0".bold(), max_plays.to_span()]),
+            )
+            .legend_position(Some(LegendPosition::TopLeft))
+            .hidden_legend_constraints((Constraint::Percentage(50), Constraint::Percentage(50)));
+
+        frame.render_widget(chart, area);
+    }
+
+    // new artists, albums and songs per period
+    fn render_discovery_chart(&mut self, frame: &mut Frame, area: Rect) {
+        let block = Block::bordered()
+            .title(Title::default().content(" Discovery rate ").alignment(Alignment::Center))
+            .padding(Padding::uniform(1));
+
+        let mut periods = self.data_discovery.iter().collect::<Vec<&Discoveries>>();
+        periods.sort_by_key(|data| data.start);
+
+        let (Some(first), Some(last)) = (periods.first(), periods.last()) else {
+            frame.render_widget(block, area);
+            return;
+        };
+
+        let counts = |count: fn(&Discoveries) -> usize| periods.iter()
+            .map(|data| (data.start.num_days_from_ce() as f64, count(data) as f64))
+            .collect::<Vec<(f64, f64)>>();
+        let series = [
+            ("Artists", counts(|data| data.artists.len())),
+            ("Albums", counts(|data| data.albums.len())),
+            ("Songs", counts(|data| data.songs.len())),
+        ];
+
+        let min_time = first.start.num_days_from_ce() as f64;
+        let max_time = last.start.num_days_from_ce() as f64;
+        let max_count = series.iter()
+            .flat_map(|(_, data)| data.iter().map(|(_, count)| *count))
+            .fold(1.0, f64::max);
+
+        let block = match periods.iter().find(|data| Some(data.start) == self.chart_point) {
+            Some(point) => block.title_bottom(Line::raw(format!(" {}: {} artists, {} albums, {} songs ",
+                point.label, point.artists.len(), point.albums.len(), point.songs.len())).centered()),
+            None => block,
+        };
+        self.areas.chart = graph_area(block.inner(area), &["0", &max_count.to_string()], &first.label);
+
+        let datasets = series.iter()
+            .zip(self.theme.series)
+            .map(|((name, data), color)| {
+                Dataset::default()
+                    .name(name.to_string())
+                    .marker(symbols::Marker::Braille)
+                    .style(Style::from(color))
+                    .graph_type(GraphType::Line)
+                    .data(data)
+            })
+            .collect::<Vec<Dataset>>();
+
+        let chart = Chart::new(datasets)
+            .block(block)
+            .x_axis(
+                Axis::default()
+                    .title(self.discovery_period.name())
+                    .bounds([min_time, max_time.max(min_time + 1.0)])
+                    .labels([first.label.clone(), last.label.clone()]),
+            )
+            .y_axis(
+                Axis::default()
+                    .title("New")
+                    .bounds([0.0, max_count])
+                    .labels(["0".bold(), max_count.to_span()]),
+            )
+            .legend_position(Some(LegendPosition::TopLeft))
+            .hidden_legend_constraints((Constraint::Percentage(50), Constraint::Percentage(50)));
+
+        frame.render_widget(chart, area);
+    }
+
+    // everything first played in the highlighted period
+    fn render_discovered(&self, frame: &mut Frame, area: Rect) {
+        let selected = self.table_state.selected()
+            .and_then(|selected| self.matches.get(selected.min(self.matches.len().saturating_sub(1))))
+            .map(|(i, _)| &self.data_discovery[*i]);
+
+        let block = Block::bordered()
+            .title(Line::raw(match selected {
+                Some(data) => format!(" New in {} ", data.label),
+                None => " New ".to_string(),
+            }).centered())
+            .padding(Padding::horizontal(1));
+
+        let Some(data) = selected else {
+            frame.render_widget(block, area);
+            return;
+        };
+
+        let section = |title: &str, names: Vec<String>| {
+            let mut lines = vec!(Line::from(Span::styled(format!("{} ({})", title, names.len()), self.theme.accent.bold())));
+            lines.extend(names.into_iter().map(|name| Line::raw(format!("  {}", name))));
+            lines.push(Line::default());
+            lines
+        };
+
+        let [artists_area, albums_area, songs_area] = Layout::horizontal([
+            Constraint::Fill(1),
+            Constraint::Fill(1),
+            Constraint::Fill(1),
+        ]).spacing(2).areas(block.inner(area));
+
+        frame.render_widget(block, area);
+        frame.render_widget(Paragraph::new(section("Artists", data.artists.clone())), artists_area);
+        frame.render_widget(Paragraph::new(section("Albums", data.albums.iter()
+            .map(|(artist, album)| format!("{} - {}", album, artist))
+            .collect())), albums_area);
+        frame.render_widget(Paragraph::new(section("Songs", data.songs.iter()
+            .map(|song| format!("{} - {}", song.title, song.artist))
+            .collect())), songs_area);
+    }
+
+    fn selected_song(&self) -> Option<&SongDataNone> {
+        let selected = self.table_state.selected()?.min(self.matches.len().checked_sub(1)?);
+        self.matches.get(selected).map(|(i, _)| &self.data_vec_none[*i])
+    }
+
+    fn render_song_detail(&mut self, frame: &mut Frame, area: Rect) {
+        let block = Block::bordered()
+            .title(Title::from(" Song Details ").alignment(Alignment::Center))
+            .padding(Padding::horizontal(1));
+
+        let Some((id, title, artist)) = self.selected_song().map(|song| (song.id, song.title.clone(), song.artist.clone())) else {
+            frame.render_widget(block, area);
+            return;
+        };
+
+        let loaded = self.song_detail.as_ref().is_some_and(|detail| detail.id == id);
+        if !loaded && self.song_detail_requested != Some(id) {
+            self.song_detail_requested = Some(id);
+            self.load(&[Part::SongDetail]);
+        }
+
+        let inner_area = block.inner(area);
+        frame.render_widget(block, area);
+
+        let Some(detail) = self.song_detail.as_ref().filter(|detail| detail.id == id) else {
+            frame.render_widget(Paragraph::new(vec!(Line::from(title.bold()), Line::from(artist))), inner_area);
+            return;
+        };
+
+        let [info_area, plays_area, rank_area] = Layout::vertical([
+            Constraint::Length(7),
+            Constraint::Length(6),
+            Constraint::Fill(1),
+        ]).areas(inner_area);
+
+        let mut lines = vec!(
+            Line::from(title.bold()),
+            Line::from(artist),
+            Line::default(),
+        );
+        if let Some(summary) = &detail.summary {
+            let listening_time = summary.listening_time.map_or("Unknown".to_string(), format_duration);
+            lines.extend([
+                Line::from(vec!(Span::styled("First played: ", self.theme.accent), summary.first_played.to_span())),
+                Line::from(vec!(Span::styled("Last played: ", self.theme.accent), summary.last_played.to_span())),
+                Line::from(vec!(Span::styled("Plays: ", self.theme.accent), summary.plays.to_span(), Span::styled("  Listened: ", self.theme.accent), listening_time.into())),
+            ]);
+        }
+        frame.render_widget(Paragraph::new(lines), info_area);
+
+        // the most recent months if they don't all fit
+        let shown = &detail.months[detail.months.len().saturating_sub(plays_area.width as usize)..];
+        let plays = shown.iter()
+            .map(|month| month.plays as u64)
+            .collect::<Vec<u64>>();
+
+        let sparkline = Sparkline::default()
+            .block(Block::new().title(format!("Plays per month ({}..{})",
+                shown.first().map_or("", |month| &month.month),
+                shown.last().map_or("", |month| &month.month))))
+            .data(&plays)
+            .style(self.theme.accent);
+        frame.render_widget(sparkline, plays_area);
+
+        // months without plays have no rank so they are skipped
+        let ranked = detail.months.iter()
+            .enumerate()
+            .filter(|(_, month)| month.rank > 0)
+            .map(|(i, month)| (i as f64, month.rank as f64))
+            .collect::<Vec<(f64, f64)>>();
+        let lowest = ranked.iter().map(|(_, rank)| *rank).fold(1.0, f64::max);
+
+        // plotted upside down so rank 1 is at the top
+        let data = ranked.iter()
+            .map(|(month, rank)| (*month, lowest + 1.0 - rank))
+            .collect::<Vec<(f64, f64)>>();
+
+        let chart = Chart::new(vec!(
+            Dataset::default()
+                .marker(symbols::Marker::Braille)
+                .graph_type(GraphType::Line)
+                .data(&data)
+        ))
+            .block(Block::new().title("Rank per month"))
+            .x_axis(Axis::default().bounds([0.0, detail.months.len().saturating_sub(1).max(1) as f64]))
+            .y_axis(
+                Axis::default()
+                    .bounds([1.0, lowest])
+                    .labels([format!("#{}", lowest), "#1".to_string()]),
+            );
+        frame.render_widget(chart, rank_area);
+    }
+
+    // one bar per row for the entries around the table selection
+    fn render_bar_chart(&mut self, frame: &mut Frame, area: Rect) {
+        let block = Block::bordered()
+            .title(
+                Title::default()
+                    .content(format!(" {} ", self.metric))
+                    .alignment(Alignment::Center),
+            )
+            .title_bottom(match self.group {
+                Group::Artist => self.title_hints(&[(&[Action::Chart], "Over time")]),
+                _ => Line::default(),
+            })
+            .padding(Padding::uniform(1));
+
+        // as many as fit, scrolled so the selected entry is always one of them
+        let shown = block.inner(area).height as usize;
+        let selected = self.table_state.selected().unwrap_or_default().min(self.matches.len().saturating_sub(1));
+        let start = (selected + 1).saturating_sub(shown);
+        let label_width = (area.width / 3).max(4) as usize;
+        self.areas.bars = block.inner(area);
+        self.areas.bars_start = start;
+
+        // labels only for the rows on screen
+        let bars = self.matches.iter()
+            .enumerate()
+            .skip(start)
+            .take(shown)
+            .map(|(row, (i, _))| {
+                let (label, measures) = match self.group {
+                    Group::Album => {
+                        let data = &self.data_vec_album[*i];
+                        (format!("{} - {}", data.album, data.artist), &data.measures)
+                    }
+                    _ => {
+                        let data = &self.data_vec_artist[*i];
+                        (data.artist.clone(), &data.measures)
+                    }
+                };
+
+                // bars are whole numbers, two decimals keep the fractional metrics apart
+                let value = (self.metric.value(measures) * 100.0) as u64;
+                let text = self.metric.format(measures);
+                let style = if row == selected { self.theme.selected } else { self.theme.accent };
+
+                Bar::default()
+                    .label(Line::from(truncate(&label, label_width)))
+                    .value(value)
+                    .text_value(text)
+                    .style(style)
+                    .value_style(style.reversed())
+            })
+            .collect::<Vec<Bar>>();
 
         let bar_chart = BarChart::default()
-            // .block(block)
+            .block(block)
             .bar_width(1)
             .bar_gap(0)
-            // .bar_style(Style::from(Color::Red))
-            .label_style(Style::new())
             .direction(Direction::Horizontal)
-            .data(&data);
+            .data(BarGroup::default().bars(&bars));
 
         frame.render_widget(bar_chart, area);
     }
 
+    fn render_calendar(&self, frame: &mut Frame, area: Rect) {
+        let border_style = match self.selected_tab {
+            SelectedTab::Table => self.theme.accent,
+            _ => Style::default(),
+        };
+
+        let year = self.calendar_cursor.year();
+        let block = Block::bordered()
+            .title(Line::raw(format!(" Calendar {} ", year)).centered())
+            .title_bottom(self.title_hints(&[
+                (&[Action::Left, Action::Right], "Week"),
+                (&[Action::Up, Action::Down], "Day"),
+                (&[Action::PrevYear, Action::NextYear], "Year"),
+                (&[Action::Time], "Plays/Time"),
+            ]))
+            .border_style(border_style)
+            .padding(Padding::uniform(1));
+
+        let values = self.data_calendar.iter()
+            .map(|(date, data)| (*date, if self.calendar_time { data.listening_time.as_secs() } else { data.plays as u64 }))
+            .collect::<HashMap<NaiveDate, u64>>();
+
+        let describe = |plays: u32, time: Duration| {
+            let plays = format!("{} play{}", plays, if plays == 1 { "" } else { "s" });
+            match time.is_zero() {
+                true => plays,
+                false => format!("{}, {}", plays, format_duration(time)),
+            }
+        };
+
+        let cursor = self.data_calendar.get(&self.calendar_cursor);
+        let tooltip = format!("{} {}: {}",
+            weekday_name(self.calendar_cursor),
+            self.calendar_cursor,
+            describe(cursor.map_or(0, |data| data.plays), cursor.map_or(Duration::ZERO, |data| data.listening_time)));
+
+        let total = format!("{} in {}, coloured by {}",
+            describe(self.data_calendar.values().map(|data| data.plays).sum(), self.data_calendar.values().map(|data| data.listening_time).sum()),
+            year,
+            if self.calendar_time { "listening time" } else { "plays" });
+
+        let mut lines = heatmap(year, &values, self.calendar_cursor, &self.theme);
+        lines.extend([Line::default(), Line::from(tooltip.bold()), Line::from(total)]);
+
+        frame.render_widget(Paragraph::new(lines).block(block), area);
+    }
+
+    // the sections split over two columns
+    fn render_wrapped(&self, frame: &mut Frame, area: Rect) {
+        let border_style = match self.selected_tab {
+            SelectedTab::Table => self.theme.accent,
+            _ => Style::default(),
+        };
+
+        let block = Block::bordered()
+            .title(Line::raw(format!(" {} ", self.data_wrapped.title())).centered())
+            .title_bottom(self.title_hints(&[(&[Action::PrevYear, Action::NextYear], "Year")]))
+            .border_style(border_style)
+            .padding(Padding::uniform(1));
+
+        let [left_area, right_area] = Layout::horizontal([
+            Constraint::Fill(1),
+            Constraint::Fill(1),
+        ]).spacing(2).areas(block.inner(area));
+
+        let sections = self.data_wrapped.sections();
+        let (left, right) = sections.split_at(sections.len().div_ceil(2));
+        let lines = |sections: &[Section]| {
+            sections.iter()
+                .flat_map(|section| {
+                    let mut lines = vec!(Line::from(Span::styled(section.title.clone(), self.theme.accent.bold())));
+                    lines.extend(section.lines.iter().map(|line| Line::raw(format!("  {}", line))));
+                    lines.push(Line::default());
+                    lines
+                })
+                .collect::<Vec<Line>>()
+        };
+
+        frame.render_widget(block, area);
+        frame.render_widget(Paragraph::new(lines(left)), left_area);
+        frame.render_widget(Paragraph::new(lines(right)), right_area);
+    }
+
+    fn render_now_playing(&self, frame: &mut Frame, area: Rect) {
+        let Some(now_playing) = &self.now_playing else {
+            return;
+        };
+
+        let block = Block::bordered()
+            .title(Title::from(format!(" Now Playing: {} ", now_playing.player)).alignment(Alignment::Left));
+
+        let [song_area, gauge_area] = Layout::horizontal([
+            Constraint::Fill(1),
+            Constraint::Fill(1),
+        ]).spacing(2).areas(block.inner(area));
+
+        let song = Line::from(vec!(
+            Span::styled(if now_playing.playing { "▶ " } else { "⏸ " }, self.theme.accent),
+            now_playing.song.title.clone().bold(),
+            format!(" - {} - {}", now_playing.song.artist, now_playing.song.album).into(),
+        ));
+
+        let seconds = |ms: i64| format!("{}:{:02}", ms / 60000, ms / 1000 % 60);
+        let label = match (&now_playing.excluded, now_playing.counted) {
+            _ if now_playing.paused => "Paused, not recorded".to_string(),
+            (Some(rule), _) => format!("Excluded by {}", rule),
+            (None, true) => "Counted".to_string(),
+            (None, false) => format!("{} / {}", seconds(now_playing.playtime_ms), seconds(now_playing.min_playtime_ms)),
+        };
+
+        let ratio = match now_playing.counted {
+            true => 1.0,
+            false => (now_playing.playtime_ms as f64 / now_playing.min_playtime_ms.max(1) as f64).clamp(0.0, 1.0),
+        };
+
+        let gauge = LineGauge::default()
+            .filled_style(self.theme.accent)
+            .label(label)
+            .ratio(ratio);
+
+        frame.render_widget(block, area);
+        frame.render_widget(Paragraph::new(song), song_area);
+        frame.render_widget(gauge, gauge_area);
+    }
+
     fn render_footer(&self, frame: &mut Frame, area: Rect) {
-        let info_footer = Paragraph::new(Line::from("(Esc/q) Quit | (Tab) Change Tab | (↑/↓) Scroll | (Pg Up/Down) Jump | (r) Refresh | (w) Weighted"))
-            .centered()
-            .block(
-                Block::bordered()
-                    .title(Title::from(" Mpressed ".red().bold()).alignment(Alignment::Center))
-                    .border_type(BorderType::Double)
-            );
+        let mut block = Block::bordered()
+            .title(Title::from(Span::styled(" Mpressed ", self.theme.accent.bold())).alignment(Alignment::Center))
+            .border_type(BorderType::Double);
+
+        let paused_title = match &self.pause_state {
+            PauseState::Recording => None,
+            PauseState::Paused(None) => Some(" Tracking paused ".to_string()),
+            PauseState::Paused(Some(until)) => Some(format!(" Tracking paused until {} ", until.format("%H:%M"))),
+        };
+
+        if self.loader.loading() {
+            block = block.title(Title::from(Span::styled(" Loading… ", self.theme.accent)).alignment(Alignment::Right));
+        } else if let Some(err) = &self.load_error {
+            block = block.title(Title::from(Span::styled(format!(" Failed to load: {} ", err), self.theme.accent.reversed())).alignment(Alignment::Right));
+        }
+
+        if let Some(paused_title) = paused_title {
+            block = block.title(Title::from(Span::styled(paused_title, self.theme.accent.reversed())).alignment(Alignment::Right));
+        }
+
+        let mut filters = [("Player", &self.filter.player), ("Device", &self.filter.device)]
+            .into_iter()
+            .filter_map(|(name, filter)| filter.as_ref().map(|filter| format!("{}: {}", name, if filter.is_empty() { "Unknown" } else { filter })))
+            .map(Span::raw)
+            .collect::<Vec<Span>>();
+
+        match self.range {
+            Range::AllTime => {}
+            Range::Custom => filters.insert(0, Span::raw(format!("Range: {}", format_bounds(&(self.filter.from, self.filter.to))))),
+            range => filters.insert(0, Span::raw(format!("Range: {}", range))),
+        }
+
+        // greyed out where the view ignores it
+        if self.metric != Metric::Plays {
+            let metric = Span::raw(format!("Metric: {}", self.metric));
+            filters.push(if self.metric_applies() { metric } else { metric.dim() });
+        }
+
+        if !self.search.is_empty() && self.prompt.is_none() {
+            filters.push(Span::raw(format!("Search: {} ({})", self.search, self.matches.len())));
+        }
+
+        if !filters.is_empty() {
+            let mut spans = vec!(Span::raw(" "));
+            for (i, filter) in filters.into_iter().enumerate() {
+                if i > 0 {
+                    spans.push(Span::raw(" | "));
+                }
+                spans.push(filter);
+            }
+            spans.push(Span::raw(" "));
+            block = block.title(Title::from(Line::from(spans)).alignment(Alignment::Left));
+        }
+
+        let info_footer = match &self.prompt {
+            Some(prompt) => {
+                let label = match prompt.kind {
+                    PromptKind::Range => "Range (FROM..TO, YYYY-MM-DD): ",
+                    PromptKind::Search => "/",
+                };
+                let mut line = Line::from(vec!(Span::styled(label, self.theme.accent), prompt.input.to_span(), "█".to_span()));
+                if prompt.invalid {
+                    line.push_span(Span::styled("  Invalid range", self.theme.accent.bold()));
+                }
+                Paragraph::new(line).block(block)
+            }
+            None => Paragraph::new(Line::from(self.help()))
+                .centered()
+                .block(block),
+        };
 
         frame.render_widget(info_footer, area);
     }
 
+    // the keys that do something in the current tab and grouping, as they are bound
+    fn help(&self) -> String {
+        let mut hints: Vec<(&[Action], &str)> = vec!((&[Action::Quit], "Quit"), (&[Action::NextTab], "Change Tab"));
+        match (&self.selected_tab, self.group) {
+            (SelectedTab::Table, Group::Calendar) => hints.extend([
+                (&[Action::Up, Action::Down, Action::Left, Action::Right][..], "Move"),
+                (&[Action::PrevYear, Action::NextYear], "Year"),
+                (&[Action::Time], "Plays/Time"),
+            ]),
+            (SelectedTab::Table, Group::Wrapped) => hints.push((&[Action::PrevYear, Action::NextYear], "Year")),
+            (SelectedTab::Table, group) => {
+                hints.extend([(&[Action::Up, Action::Down][..], "Scroll"), (&[Action::Top, Action::Bottom], "Jump")]);
+                if matches!(group, Group::None | Group::Artist | Group::Album) {
+                    hints.push((&[Action::Metric], "Metric"));
+                }
+                hints.extend([
+                    (&[Action::SortColumn, Action::SortDirection][..], "Sort Column/Direction"),
+                    (&[Action::NextMatch, Action::PrevMatch], "Next/Prev"),
+                    (&[Action::Select, Action::Back], "Drill Down/Up"),
+                ]);
+                match group {
+                    Group::Date | Group::Discovery => hints.push((&[Action::Period], "Period")),
+                    Group::Artist if self.trend_shown => hints.extend([
+                        (&[Action::Chart][..], "Chart"),
+                        (&[Action::Toggle], "Compare"),
+                        (&[Action::Period], "Period"),
+                    ]),
+                    Group::Artist => hints.extend([(&[Action::Chart][..], "Chart"), (&[Action::Toggle], "Compare")]),
+                    _ => {}
+                }
+            }
+            (SelectedTab::Sort, _) => hints.extend([
+                (&[Action::Up, Action::Down][..], "Move"),
+                (&[Action::Select], "Select"),
+                (&[Action::Toggle], "Reverse"),
+            ]),
+            (SelectedTab::Range, _) => hints.extend([(&[Action::Up, Action::Down][..], "Move"), (&[Action::Select], "Custom")]),
+            (SelectedTab::Group, _) => hints.push((&[Action::Up, Action::Down], "Move")),
+        }
+        hints.extend([
+            (&[Action::Refresh][..], "Refresh"),
+            (&[Action::Player, Action::Device], "Player/Device"),
+            (&[Action::Search], "Search"),
+            (&[Action::Mouse], "Mouse"),
+        ]);
+
+        self.key_hints(&hints)
+    }
+
+    // the same hints as the footer, for the bottom of a widget
+    fn title_hints(&self, hints: &[(&[Action], &str)]) -> Line<'static> {
+        match self.key_hints(hints) {
+            hints if hints.is_empty() => Line::default(),
+            hints => Line::raw(format!(" {} ", hints)).centered(),
+        }
+    }
+
+    // "(key/key) label" for each hint, leaving out actions that have no key bound
+    fn key_hints(&self, hints: &[(&[Action], &str)]) -> String {
+        hints.iter()
+            .filter_map(|(actions, label)| {
+                let keys = actions.iter()
+                    .filter_map(|action| self.keymap.key(*action))
+                    .map(|key| key.to_string())
+                    .collect::<Vec<String>>();
+                (!keys.is_empty()).then(|| format!("({}) {}", keys.join("/"), label))
+            })
+            .collect::<Vec<String>>()
+            .join(" | ")
+    }
+
     fn handle_events(&mut self) -> Result<()> {
-        if let Event::Key(key_event) = event::read()? {
-            match key_event.code {
-                KeyCode::BackTab => self.selected_tab_prev(),
-                KeyCode::Tab => self.selected_tab_next(),
-                KeyCode::Char('r') => self.update_data(),
-                KeyCode::Esc | KeyCode::Char('q') => self.exit(),
+        let event = event::read()?;
+        if let Event::Mouse(mouse_event) = event {
+            if self.prompt.is_none() {
+                self.handle_mouse(mouse_event);
+            }
+            return Ok(());
+        }
+
+        if let Event::Key(key_event) = event {
+            // typing into the prompt shouldn't trigger any shortcuts
+            if let Some(prompt) = &mut self.prompt {
+                match key_event.code {
+                    KeyCode::Enter => self.prompt_submit(),
+                    KeyCode::Esc => {
+                        if let Some(Prompt { kind: PromptKind::Search, .. }) = self.prompt.take() {
+                            self.search_changed(String::new());
+                        }
+                    }
+                    KeyCode::Backspace => {
+                        prompt.input.pop();
+                        prompt.invalid = false;
+                    }
+                    KeyCode::Char(char) => {
+                        prompt.input.push(char);
+                        prompt.invalid = false;
+                    }
+                    _ => {}
+                }
+
+                // search filters the rows as it is typed
+                if let Some(Prompt { kind: PromptKind::Search, input, .. }) = &self.prompt {
+                    if *input != self.search {
+                        self.search_changed(input.clone());
+                    }
+                }
+                return Ok(());
+            }
+
+            let Some(action) = self.keymap.action(key_event) else {
+                return Ok(());
+            };
+
+            match action {
+                Action::PrevTab => self.selected_tab_prev(),
+                Action::NextTab => self.selected_tab_next(),
+                Action::Refresh => self.refresh(),
+                Action::Player => self.player_filter_next(),
+                Action::Device => self.device_filter_next(),
+                Action::Search => self.search_start(),
+                Action::Mouse => self.mouse_toggle()?,
+                Action::Back => self.drill_up(),
+                Action::Quit => self.exit(),
                 _ => {}
             }
             match self.selected_tab {
+                SelectedTab::Table if self.group == Group::Calendar => {
+                    match action {
+                        Action::Up => self.calendar_move(-1),
+                        Action::Down => self.calendar_move(1),
+                        Action::Left => self.calendar_move(-7),
+                        Action::Right => self.calendar_move(7),
+                        Action::PrevYear => self.calendar_year(-1),
+                        Action::NextYear => self.calendar_year(1),
+                        Action::Time => self.calendar_time = !self.calendar_time,
+                        _ => {}
+                    }
+                }
+                SelectedTab::Table if self.group == Group::Wrapped => {
+                    match action {
+                        Action::PrevYear => self.wrapped_year(-1),
+                        Action::NextYear => self.wrapped_year(1),
+                        _ => {}
+                    }
+                }
                 SelectedTab::Table => {
-                    match key_event.code {
-                        KeyCode::Up => self.table_up(),
-                        KeyCode::Down => self.table_down(),
-                        KeyCode::PageUp => self.table_start(),
-                        KeyCode::PageDown => self.table_end(),
-                        KeyCode::Char('w') => {
-                            self.weighted = !self.weighted;
-                            self.data_vec_artist.sort_by(|a, b| {
-                                match self.weighted {
-                                    true => b.plays_weighted.total_cmp(&a.plays_weighted),
-                                    false => b.plays.cmp(&a.plays),
-                                }
-                            });
-                            self.data_vec_album.sort_by(|a, b| {
-                                match self.weighted {
-                                    true => b.plays_weighted.total_cmp(&a.plays_weighted),
-                                    false => b.plays.cmp(&a.plays),
-                                }
-                            });
-                        },
+                    match action {
+                        Action::Up => self.table_up(),
+                        Action::Down => self.table_down(),
+                        Action::Top => self.table_start(),
+                        Action::Bottom => self.table_end(),
+                        Action::HalfPageUp => self.table_half_page(false),
+                        Action::HalfPageDown => self.table_half_page(true),
+                        Action::Select => self.drill_down(),
+                        Action::Period if self.group == Group::Date => self.date_period_next(),
+                        Action::Period if self.group == Group::Discovery => self.discovery_period_next(),
+                        Action::Period if self.group == Group::Artist && self.trend_shown => self.trend_period_next(),
+                        Action::Chart if self.group == Group::Artist => self.trend_toggle(),
+                        Action::Toggle if self.group == Group::Artist => self.compare_toggle(),
+                        Action::NextMatch => self.search_jump(true),
+                        Action::PrevMatch => self.search_jump(false),
+                        Action::SortColumn => self.sort_column_next(),
+                        Action::SortDirection => self.sort_reverse_primary(),
+                        Action::Metric => self.metric_next(),
                         _ => {}
                     }
                 }
                 SelectedTab::Sort => {
-                    match key_event.code {
-                        KeyCode::Up => self.sort_prev(),
-                        KeyCode::Down => self.sort_next(),
-                        KeyCode::Enter => self.sort_select(),
+                    match action {
+                        Action::Up => self.sort_prev(),
+                        Action::Down => self.sort_next(),
+                        Action::Select => self.sort_select(),
+                        Action::Toggle => self.sort_reverse(),
                         _ => {}
                     }
                 }
                 SelectedTab::Group => {
-                    match key_event.code {
-                        KeyCode::Up => self.group_prev(),
-                        KeyCode::Down => self.group_next(),
+                    match action {
+                        Action::Up => self.group_prev(),
+                        Action::Down => self.group_next(),
+                        _ => {}
+                    }
+                }
+                SelectedTab::Range => {
+                    match action {
+                        Action::Up => self.range_prev(),
+                        Action::Down => self.range_next(),
+                        Action::Select if self.range_cursor() == Range::Custom => self.range_custom(),
                         _ => {}
                     }
                 }
@@ -769,6 +2199,94 @@ impl TuiState {
         Ok(())
     }
 
+    fn handle_mouse(&mut self, mouse_event: MouseEvent) {
+        match mouse_event.kind {
+            MouseEventKind::Down(MouseButton::Left) => self.click(Position::new(mouse_event.column, mouse_event.row)),
+            MouseEventKind::ScrollUp => self.table_up(),
+            MouseEventKind::ScrollDown => self.table_down(),
+            _ => {}
+        }
+    }
+
+    fn click(&mut self, position: Position) {
+        if let Some(line) = line_at(self.areas.group, position) {
+            self.selected_tab = SelectedTab::Group;
+            if let Some(group) = Group::ALL.get(line + self.group_state.offset()) {
+                self.group_select(*group);
+            }
+        } else if let Some(line) = line_at(self.areas.range, position) {
+            self.selected_tab = SelectedTab::Range;
+            let i = line + self.range_state.offset();
+            if i < Range::ALL.len() {
+                self.range_state.select(Some(i));
+                match self.range_cursor() {
+                    Range::Custom => self.range_custom(),
+                    _ => self.range_select(),
+                }
+            }
+        } else if let Some(line) = line_at(self.areas.sort, position) {
+            self.selected_tab = SelectedTab::Sort;
+            let i = line + self.sort_state.offset();
+            if i < self.sort_priority().len() {
+                self.sort_state.select(Some(i));
+                self.sort_select();
+            }
+        } else if let Some(line) = line_at(self.areas.table, position) {
+            self.selected_tab = SelectedTab::Table;
+            match line.checked_sub(HEADER_HEIGHT as usize) {
+                Some(row) => self.table_select(self.table_state.offset() + row),
+                None => {
+                    if let Some(&sort) = column_at(&self.areas.columns, position.x).and_then(|column| self.group.columns().get(column)) {
+                        self.sort_by(sort);
+                    }
+                }
+            }
+        } else if let Some(line) = line_at(self.areas.bars, position) {
+            self.table_select(self.areas.bars_start + line);
+        } else if self.areas.chart.contains(position) {
+            self.chart_click(fraction_at(self.areas.chart, position.x));
+        }
+    }
+
+    // picks the period closest to fraction of the way across the chart
+    fn chart_click(&mut self, fraction: f64) {
+        let mut starts = match self.group {
+            Group::Date => self.data_vec_date.iter().map(|data| data.start).collect(),
+            Group::Discovery => self.data_discovery.iter().map(|data| data.start).collect(),
+            Group::Artist => self.data_trend.first().map_or(vec!(), |(_, periods)| periods.iter().map(|period| period.start).collect()),
+            _ => vec!(),
+        };
+        starts.sort();
+
+        let (Some(first), Some(last)) = (starts.first(), starts.last()) else {
+            return;
+        };
+        let days = starts.iter().map(|start| start.num_days_from_ce() as f64).collect::<Vec<f64>>();
+        let x = first.num_days_from_ce() as f64 + fraction * (last.num_days_from_ce() - first.num_days_from_ce()) as f64;
+        let Some(point) = nearest(&days, x).map(|i| starts[i]) else {
+            return;
+        };
+
+        self.chart_point = Some(point);
+        // the table follows along in the Date and Discovery groupings
+        let row = match self.group {
+            Group::Date => self.matches.iter().position(|(i, _)| self.data_vec_date[*i].start == point),
+            Group::Discovery => self.matches.iter().position(|(i, _)| self.data_discovery[*i].start == point),
+            _ => None,
+        };
+        if let Some(row) = row {
+            self.table_select(row);
+        }
+    }
+
+    fn mouse_toggle(&mut self) -> Result<()> {
+        self.mouse_captured = !self.mouse_captured;
+        match self.mouse_captured {
+            true => execute!(io::stdout(), EnableMouseCapture),
+            false => execute!(io::stdout(), DisableMouseCapture),
+        }
+    }
+
     fn exit(&mut self) {
         self.exit = true;
     }
@@ -791,6 +2309,26 @@ impl TuiState {
         self.scroll_state.next();
     }
 
+    fn table_select(&mut self, row: usize) {
+        if row < self.matches.len() {
+            self.table_state.select(Some(row));
+            self.scroll_state = self.scroll_state.position(row);
+        }
+    }
+
+    // half of the rows that fit in the table
+    fn table_half_page(&mut self, down: bool) {
+        let amount = (self.areas.table.height.saturating_sub(HEADER_HEIGHT) / 2).max(1);
+        match down {
+            true => self.table_state.scroll_down_by(amount),
+            false => self.table_state.scroll_up_by(amount),
+        }
+
+        let selected = self.table_state.selected().unwrap_or_default().min(self.matches.len().saturating_sub(1));
+        self.table_state.select(Some(selected));
+        self.scroll_state = self.scroll_state.position(selected);
+    }
+
     fn table_start(&mut self) {
         self.table_state.select_first();
         self.scroll_state.first();
@@ -809,42 +2347,96 @@ impl TuiState {
         self.sort_state.select_next();
     }
 
-    fn sort_select(&mut self) {
+    // index into the current grouping's sort keys of the one highlighted in the sidebar
+    fn sort_cursor(&self) -> Option<usize> {
+        let len = self.sort_priority().len();
         // subtract because sorting_priority is reversed compared to sorting_state
-        let s = self.sort_priority.len() - self.sort_state.selected().unwrap() - 1;
-        let temp: SortDirection = self.sort_priority.remove(s);
-        self.sort_priority.push(temp);
+        self.sort_state.selected()
+            .filter(|selected| *selected < len)
+            .map(|selected| len - selected - 1)
+    }
+
+    fn sort_select(&mut self) {
+        let Some(s) = self.sort_cursor() else {
+            return;
+        };
+
+        let priority = self.sorts.get_mut(&self.group).unwrap();
+        let temp: SortDirection = priority.remove(s);
+        priority.push(temp);
         self.data_sort();
     }
 
+    fn sort_reverse(&mut self) {
+        let Some(s) = self.sort_cursor() else {
+            return;
+        };
+
+        let priority = self.sorts.get_mut(&self.group).unwrap();
+        priority[s].1 = !priority[s].1;
+        self.data_sort();
+    }
+
+    fn group_select(&mut self, group: Group) {
+        self.set_group(group);
+        self.table_state.select_first();
+        self.update_matches();
+        self.scroll_reset();
+        self.load_stale();
+    }
+
     fn group_prev(&mut self) {
         self.group.prev();
         self.group_state.select_previous();
         self.table_state.select_first();
+        self.update_matches();
         self.scroll_reset();
+        self.load_stale();
     }
 
     fn group_next(&mut self) {
         self.group.next();
         self.group_state.select_next();
         self.table_state.select_first();
+        self.update_matches();
         self.scroll_reset();
+        self.load_stale();
+    }
+
+    fn set_group(&mut self, group: Group) {
+        self.group = group;
+        self.group_state.select(Some(group as usize));
     }
 
     fn scroll_reset(&mut self) {
-        self.scroll_state = ScrollbarState::new(
-            match self.group {
-                Group::None => self.data_vec_none.len(),
-                Group::Date => self.data_vec_date.len(),
-                Group::Artist => self.data_vec_artist.len(),
-                Group::Album => self.data_vec_album.len(),
-            }
-        );
+        self.scroll_state = ScrollbarState::new(self.matches.len());
+    }
+
+}
+
+// cut to width characters, ending in … when anything was cut
+fn truncate(text: &str, width: usize) -> String {
+    match text.chars().count() > width {
+        true => text.chars().take(width.saturating_sub(1)).chain(['…']).collect(),
+        false => text.to_string(),
     }
+}
 
+// players and devices recorded before they were tracked are stored as ''
+fn source_name(source: &str) -> &str {
+    if source.is_empty() { "Unknown" } else { source }
 }
 
+const USAGE: &str = "Usage: mpressed [wrapped [YEAR] [--markdown]]
+  wrapped prints the year in review for YEAR (default this year) as plain text or Markdown";
+
 fn main() -> Result<()> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if !args.is_empty() {
+        run_command(&args);
+        return Ok(());
+    }
+
     // setup terminal
     enable_raw_mode()?;
     let mut stdout = io::stdout();
@@ -853,7 +2445,11 @@ fn main() -> Result<()> {
     let mut terminal = Terminal::new(backend)?;
 
     // create app and run it
-    let mut tui_state = TuiState::new();
+    let mut tui_state = TuiState::new(&Config::load());
+    // search matches are coloured outside of the theme
+    if !tui_state.theme.colour {
+        force_color_output(false);
+    }
     let res = tui_state.run(&mut terminal);
 
     // restore terminal
@@ -868,3 +2464,33 @@ fn main() -> Result<()> {
     Ok(())
 }
 
+fn run_command(args: &[String]) {
+    match args[0].as_str() {
+        "wrapped" => wrapped_command(&args[1..]),
+        _ => {
+            eprintln!("{}", USAGE);
+            exit(1);
+        }
+    }
+}
+
+fn wrapped_command(args: &[String]) {
+    let markdown = args.iter().any(|arg| arg == "--markdown");
+    let year = match args.iter().find(|arg| *arg != "--markdown") {
+        Some(year) => year.parse().unwrap_or_else(|_| {
+            eprintln!("{}", USAGE);
+            exit(1);
+        }),
+        None => Local::now().year(),
+    };
+
+    let stats = Stats::open(get_db_path()).expect("Failed to open database");
+    match wrapped(&stats, year, &Filter::default()) {
+        Ok(wrapped) if markdown => print!("{}", wrapped.to_markdown()),
+        Ok(wrapped) => print!("{}", wrapped.to_text()),
+        Err(err) => {
+            eprintln!("Failed to read the database: {}", err);
+            exit(1);
+        }
+    }
+}
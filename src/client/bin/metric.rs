@@ -0,0 +1,154 @@
+use std::time::Duration;
+use strum::Display;
+use mpressed::format_duration;
+
+// what songs, artists and albums are ranked by, in their tables and the bar chart
+#[derive(Clone, Copy, Debug, Default, Display, PartialEq)]
+pub enum Metric {
+    #[default]
+    Plays,
+    #[strum(to_string = "Listening time")]
+    ListeningTime,
+    // of every play in the table
+    Share,
+    // share of the plays divided over every song known, so big catalogues don't win on size alone
+    #[strum(to_string = "Weighted plays")]
+    Weighted,
+    // distinct songs played
+    Songs,
+    #[strum(to_string = "Plays per song")]
+    PlaysPerSong,
+    // recent plays count more, see RECENCY_SCALE_DAYS
+    Recency,
+}
+
+impl Metric {
+    pub fn next(self) -> Self {
+        match self {
+            Metric::Plays => Metric::ListeningTime,
+            Metric::ListeningTime => Metric::Share,
+            Metric::Share => Metric::Weighted,
+            Metric::Weighted => Metric::Songs,
+            Metric::Songs => Metric::PlaysPerSong,
+            Metric::PlaysPerSong => Metric::Recency,
+            Metric::Recency => Metric::Plays,
+        }
+    }
+
+    // short enough for a table column
+    pub fn heading(self) -> &'static str {
+        match self {
+            Metric::Plays => "Plays",
+            Metric::ListeningTime => "Time",
+            Metric::Share => "Share",
+            Metric::Weighted => "Weighted",
+            Metric::Songs => "Songs",
+            Metric::PlaysPerSong => "Per song",
+            Metric::Recency => "Score",
+        }
+    }
+
+    pub fn value(self, measures: &Measures) -> f64 {
+        match self {
+            Metric::Plays => measures.plays as f64,
+            Metric::ListeningTime => measures.listening_time.as_secs_f64(),
+            Metric::Share => measures.share,
+            Metric::Weighted => measures.weighted,
+            Metric::Songs => measures.songs as f64,
+            Metric::PlaysPerSong => measures.plays as f64 / measures.songs.max(1) as f64,
+            Metric::Recency => measures.recency,
+        }
+    }
+
+    pub fn format(self, measures: &Measures) -> String {
+        let value = self.value(measures);
+        match self {
+            Metric::Plays | Metric::Songs => format!("{}", value),
+            Metric::ListeningTime => format_duration(measures.listening_time),
+            Metric::Share | Metric::Weighted => format!("{:.2}%", value * 100.0),
+            Metric::PlaysPerSong | Metric::Recency => format!("{:.2}", value),
+        }
+    }
+}
+
+// everything a row can be ranked on
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Measures {
+    pub plays: u32,
+    // only songs whose length is known add to it
+    pub listening_time: Duration,
+    pub songs: u32,
+    // plays as a fraction of every play in the table, 0 to 1
+    pub share: f64,
+    // plays per song known, then as a fraction like share
+    pub weighted: f64,
+    pub recency: f64,
+}
+
+impl Measures {
+    pub fn new(plays: u32, listening_time: Duration, songs: u32, recency: f64) -> Self {
+        Self {
+            plays,
+            listening_time,
+            songs,
+            share: 0.0,
+            weighted: plays as f64,
+            recency,
+        }
+    }
+
+    // every song known for the artist or album, played in the range or not
+    pub fn weighted_by(mut self, known_songs: u32) -> Self {
+        self.weighted = self.plays as f64 / known_songs.max(1) as f64;
+        self
+    }
+}
+
+// sets every row's share of the plays, and of the weighted plays, of all of them
+pub fn set_shares<'a>(measures: impl Iterator<Item = &'a mut Measures>) {
+    let measures: Vec<&mut Measures> = measures.collect();
+    let total: u32 = measures.iter().map(|measures| measures.plays).sum();
+    let total_weighted: f64 = measures.iter().map(|measures| measures.weighted).sum();
+    for measures in measures {
+        measures.share = measures.plays as f64 / total.max(1) as f64;
+        if total_weighted > 0.0 {
+            measures.weighted /= total_weighted;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_and_formats() {
+        let measures = Measures { share: 0.125, weighted: 0.5, ..Measures::new(6, Duration::from_secs(90), 4, 2.5) };
+
+        assert_eq!(Metric::PlaysPerSong.value(&measures), 1.5);
+        let formats: Vec<String> = [Metric::Plays, Metric::ListeningTime, Metric::Share, Metric::Weighted, Metric::Songs, Metric::PlaysPerSong, Metric::Recency]
+            .map(|metric| metric.format(&measures))
+            .into();
+        assert_eq!(formats, ["6", "1m 30s", "12.50%", "50.00%", "4", "1.50", "2.50"]);
+    }
+
+    #[test]
+    fn shares_add_up() {
+        // the first has three times the plays but six times the songs
+        let mut rows = [Measures::new(3, Duration::ZERO, 1, 0.0).weighted_by(6), Measures::new(1, Duration::ZERO, 1, 0.0)];
+        set_shares(rows.iter_mut());
+        assert_eq!(rows.clone().map(|row| row.share), [0.75, 0.25]);
+        assert_eq!(rows.map(|row| row.weighted), [1.0 / 3.0, 2.0 / 3.0]);
+
+        set_shares([].iter_mut());
+    }
+
+    #[test]
+    fn cycles_back_to_plays() {
+        let mut metric = Metric::default();
+        for _ in 0..7 {
+            metric = metric.next();
+        }
+        assert_eq!(metric, Metric::Plays);
+    }
+}
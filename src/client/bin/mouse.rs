@@ -0,0 +1,133 @@
+use std::ops::Range;
+use ratatui::layout::{Constraint, Layout, Position, Rect};
+
+// lines the table header takes, the rows start below it
+pub const HEADER_HEIGHT: u16 = 2;
+
+// where the clickable parts were drawn in the last frame, inside their borders and padding
+#[derive(Debug, Default)]
+pub struct Areas {
+    pub group: Rect,
+    pub range: Rect,
+    pub sort: Rect,
+    // header included
+    pub table: Rect,
+    pub columns: Vec<Rect>,
+    // the plotting area of the Date or artists over time chart
+    pub chart: Rect,
+    // one bar per line, the first of them is entry bars_start
+    pub bars: Rect,
+    pub bars_start: usize,
+}
+
+// the line of area position is on, 0 for its top line
+pub fn line_at(area: Rect, position: Position) -> Option<usize> {
+    area.contains(position).then(|| (position.y - area.y) as usize)
+}
+
+pub fn column_at(columns: &[Rect], x: u16) -> Option<usize> {
+    columns.iter().position(|column| (column.x..column.right()).contains(&x))
+}
+
+// the same split Table makes with its default column spacing
+pub fn table_columns(area: Rect, widths: &[Constraint]) -> Vec<Rect> {
+    Layout::horizontal(widths.iter().copied()).spacing(1).split(area).to_vec()
+}
+
+// the rows drawn in a table with room for height of them, scrolled from offset just enough
+// to show selected, the same way Table scrolls
+pub fn visible_rows(offset: usize, selected: Option<usize>, len: usize, height: usize) -> Range<usize> {
+    let height = height.max(1);
+    let mut offset = offset.min(len.saturating_sub(height));
+    if let Some(selected) = selected.map(|selected| selected.min(len.saturating_sub(1))) {
+        if selected < offset {
+            offset = selected;
+        } else if selected >= offset + height {
+            offset = selected + 1 - height;
+        }
+    }
+    offset..(offset + height).min(len)
+}
+
+// what Chart leaves for plotting in area, the y labels and axis are left of it and the
+// x labels and axis below it, the first x label can stick out left of the y axis
+pub fn graph_area(area: Rect, y_labels: &[&str], first_x_label: &str) -> Rect {
+    let labels_width = y_labels.iter()
+        .map(|label| label.chars().count())
+        .chain([first_x_label.chars().count().saturating_sub(1)])
+        .max()
+        .unwrap_or_default() as u16;
+    let left = labels_width.min(area.width / 3) + 1;
+
+    Rect {
+        x: area.x + left,
+        y: area.y,
+        width: area.width.saturating_sub(left),
+        height: area.height.saturating_sub(2),
+    }
+}
+
+// 0 at the left edge of area and 1 at the right edge
+pub fn fraction_at(area: Rect, x: u16) -> f64 {
+    match area.width > 1 {
+        true => x.saturating_sub(area.x).min(area.width - 1) as f64 / (area.width - 1) as f64,
+        false => 0.0,
+    }
+}
+
+// index of the point closest to x
+pub fn nearest(points: &[f64], x: f64) -> Option<usize> {
+    points.iter()
+        .enumerate()
+        .min_by(|(_, a), (_, b)| (*a - x).abs().total_cmp(&(*b - x).abs()))
+        .map(|(i, _)| i)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines_and_columns() {
+        let area = Rect::new(2, 3, 10, 4);
+        assert_eq!(line_at(area, Position::new(5, 3)), Some(0));
+        assert_eq!(line_at(area, Position::new(11, 6)), Some(3));
+        assert_eq!(line_at(area, Position::new(5, 7)), None);
+        assert_eq!(line_at(area, Position::new(1, 4)), None);
+
+        let columns = table_columns(Rect::new(0, 0, 21, 1), &[Constraint::Fill(1), Constraint::Length(10)]);
+        assert_eq!(columns, [Rect::new(0, 0, 10, 1), Rect::new(11, 0, 10, 1)]);
+        assert_eq!(column_at(&columns, 9), Some(0));
+        assert_eq!(column_at(&columns, 10), None);
+        assert_eq!(column_at(&columns, 11), Some(1));
+    }
+
+    #[test]
+    fn scrolls_to_the_selected_row() {
+        assert_eq!(visible_rows(0, Some(3), 100, 10), 0..10);
+        assert_eq!(visible_rows(0, Some(15), 100, 10), 6..16);
+        assert_eq!(visible_rows(20, Some(15), 100, 10), 15..25);
+        assert_eq!(visible_rows(95, None, 100, 10), 90..100);
+        assert_eq!(visible_rows(5, Some(200), 4, 10), 0..4);
+        assert_eq!(visible_rows(0, Some(7), 100, 0), 7..8);
+    }
+
+    #[test]
+    fn graph_leaves_room_for_labels() {
+        let area = Rect::new(0, 0, 60, 20);
+        assert_eq!(graph_area(area, &["0", "120"], "2024-01"), Rect::new(7, 0, 53, 18));
+        assert_eq!(graph_area(area, &["0", "120"], "2024"), Rect::new(4, 0, 56, 18));
+    }
+
+    #[test]
+    fn nearest_point() {
+        let area = Rect::new(10, 0, 11, 1);
+        assert_eq!(fraction_at(area, 10), 0.0);
+        assert_eq!(fraction_at(area, 15), 0.5);
+        assert_eq!(fraction_at(area, 30), 1.0);
+
+        assert_eq!(nearest(&[0.0, 10.0, 20.0], 14.0), Some(1));
+        assert_eq!(nearest(&[0.0, 10.0, 20.0], 16.0), Some(2));
+        assert_eq!(nearest(&[], 16.0), None);
+    }
+}
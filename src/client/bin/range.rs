@@ -0,0 +1,121 @@
+use chrono::{Datelike, Months, NaiveDate, TimeDelta};
+use strum::Display;
+
+pub type Bounds = (Option<NaiveDate>, Option<NaiveDate>);
+
+#[derive(Clone, Copy, Debug, Default, Display, PartialEq)]
+pub enum Range {
+    Today,
+    #[strum(to_string = "Last 7 days")]
+    Last7Days,
+    #[strum(to_string = "This month")]
+    ThisMonth,
+    #[strum(to_string = "Last 12 months")]
+    Last12Months,
+    #[strum(to_string = "This year")]
+    ThisYear,
+    #[default]
+    #[strum(to_string = "All time")]
+    AllTime,
+    Custom,
+}
+
+impl Range {
+    // in the order they are listed in the sidebar
+    pub const ALL: [Range; 7] = [
+        Range::Today,
+        Range::Last7Days,
+        Range::ThisMonth,
+        Range::Last12Months,
+        Range::ThisYear,
+        Range::AllTime,
+        Range::Custom,
+    ];
+
+    // None for Custom, which has no bounds of its own
+    pub fn bounds(self, today: NaiveDate) -> Option<Bounds> {
+        let from = match self {
+            Range::Today => today,
+            Range::Last7Days => today - TimeDelta::days(6),
+            Range::ThisMonth => today.with_day(1)?,
+            Range::Last12Months => today.checked_sub_months(Months::new(12))? + TimeDelta::days(1),
+            Range::ThisYear => today.with_ordinal(1)?,
+            Range::AllTime => return Some((None, None)),
+            Range::Custom => return None,
+        };
+        Some((Some(from), Some(today)))
+    }
+}
+
+// FROM..TO where either side may be left out, or a single DATE
+pub fn parse_bounds(text: &str) -> Option<Bounds> {
+    let date = |text: &str| -> Option<Option<NaiveDate>> {
+        match text.trim() {
+            "" => Some(None),
+            text => NaiveDate::parse_from_str(text, "%Y-%m-%d").ok().map(Some),
+        }
+    };
+
+    let (from, to) = match text.split_once("..") {
+        Some((from, to)) => (date(from)?, date(to)?),
+        None => {
+            let day = date(text)?;
+            (day, day)
+        }
+    };
+
+    match (from, to) {
+        (Some(from), Some(to)) if from > to => None,
+        _ => Some((from, to)),
+    }
+}
+
+pub fn format_bounds(bounds: &Bounds) -> String {
+    match bounds {
+        (Some(from), Some(to)) if from == to => from.to_string(),
+        (from, to) => format!(
+            "{}..{}",
+            from.map(|date| date.to_string()).unwrap_or_default(),
+            to.map(|date| date.to_string()).unwrap_or_default()
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(text: &str) -> NaiveDate {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn preset_bounds() {
+        let today = date("2024-03-15");
+
+        assert_eq!(Range::Today.bounds(today), Some((Some(today), Some(today))));
+        assert_eq!(Range::Last7Days.bounds(today), Some((Some(date("2024-03-09")), Some(today))));
+        assert_eq!(Range::ThisMonth.bounds(today), Some((Some(date("2024-03-01")), Some(today))));
+        assert_eq!(Range::Last12Months.bounds(today), Some((Some(date("2023-03-16")), Some(today))));
+        assert_eq!(Range::ThisYear.bounds(today), Some((Some(date("2024-01-01")), Some(today))));
+        assert_eq!(Range::AllTime.bounds(today), Some((None, None)));
+        assert_eq!(Range::Custom.bounds(today), None);
+    }
+
+    #[test]
+    fn parse_custom_bounds() {
+        assert_eq!(parse_bounds("2024-01-01..2024-02-01"), Some((Some(date("2024-01-01")), Some(date("2024-02-01")))));
+        assert_eq!(parse_bounds("2024-01-01.."), Some((Some(date("2024-01-01")), None)));
+        assert_eq!(parse_bounds(" .. 2024-02-01"), Some((None, Some(date("2024-02-01")))));
+        assert_eq!(parse_bounds("2024-01-01"), Some((Some(date("2024-01-01")), Some(date("2024-01-01")))));
+        assert_eq!(parse_bounds("2024-02-01..2024-01-01"), None);
+        assert_eq!(parse_bounds("January"), None);
+    }
+
+    #[test]
+    fn format_round_trips() {
+        for text in ["2024-01-01..2024-02-01", "2024-01-01..", "..2024-02-01", "2024-01-01"] {
+            assert_eq!(format_bounds(&parse_bounds(text).unwrap()), text);
+        }
+    }
+}
@@ -0,0 +1,109 @@
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+
+const MATCH_STYLE: Style = Style::new()
+    .add_modifier(Modifier::BOLD)
+    .add_modifier(Modifier::UNDERLINED)
+    .fg(Color::Yellow);
+
+fn fold(char: char) -> char {
+    char.to_lowercase().next().unwrap_or(char)
+}
+
+// char positions in text of term's characters in order, ignoring case
+pub fn fuzzy_find(term: &str, text: &str) -> Option<Vec<usize>> {
+    let mut term = term.chars().map(fold).peekable();
+    let mut positions = vec!();
+
+    for (i, char) in text.chars().enumerate() {
+        match term.peek() {
+            Some(next) if *next == fold(char) => {
+                positions.push(i);
+                term.next();
+            }
+            Some(_) => {}
+            None => break,
+        }
+    }
+
+    match term.peek() {
+        None => Some(positions),
+        Some(_) => None,
+    }
+}
+
+// every whitespace separated term has to be found in one of the fields,
+// returns the matched positions for each field
+pub fn fuzzy_match(query: &str, fields: &[&str]) -> Option<Vec<Vec<usize>>> {
+    let mut highlights = vec![vec!(); fields.len()];
+
+    for term in query.split_whitespace() {
+        let (field, positions) = fields.iter()
+            .enumerate()
+            .find_map(|(i, field)| fuzzy_find(term, field).map(|positions| (i, positions)))?;
+        highlights[field].extend(positions);
+    }
+
+    Some(highlights)
+}
+
+pub fn highlight(text: &str, positions: &[usize]) -> Line<'static> {
+    if positions.is_empty() {
+        return Line::raw(text.to_string());
+    }
+
+    let mut spans: Vec<Span> = vec!();
+    let mut run = String::new();
+    let mut run_matched = false;
+
+    for (i, char) in text.chars().enumerate() {
+        let matched = positions.contains(&i);
+        if matched != run_matched && !run.is_empty() {
+            spans.push(span(std::mem::take(&mut run), run_matched));
+        }
+        run_matched = matched;
+        run.push(char);
+    }
+    spans.push(span(run, run_matched));
+
+    Line::from(spans)
+}
+
+fn span(text: String, matched: bool) -> Span<'static> {
+    match matched {
+        true => Span::styled(text, MATCH_STYLE),
+        false => Span::raw(text),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_characters_in_order() {
+        assert_eq!(fuzzy_find("rdh", "Radiohead"), Some(vec!(0, 2, 5)));
+        assert_eq!(fuzzy_find("RADIO", "radiohead"), Some(vec!(0, 1, 2, 3, 4)));
+        assert_eq!(fuzzy_find("", "Radiohead"), Some(vec!()));
+        assert_eq!(fuzzy_find("hr", "Radiohead"), None);
+    }
+
+    #[test]
+    fn terms_can_match_different_fields() {
+        let fields = ["Radiohead", "OK Computer", "Airbag"];
+
+        assert_eq!(fuzzy_match("radio air", &fields), Some(vec!(vec!(0, 1, 2, 3, 4), vec!(), vec!(0, 1, 2))));
+        assert_eq!(fuzzy_match("radio creep", &fields), None);
+        assert_eq!(fuzzy_match("", &fields), Some(vec!(vec!(), vec!(), vec!())));
+    }
+
+    #[test]
+    fn highlights_matched_runs() {
+        let line = highlight("Airbag", &[0, 1, 4]);
+        let spans: Vec<&str> = line.spans.iter().map(|span| span.content.as_ref()).collect();
+
+        assert_eq!(spans, ["Ai", "rb", "a", "g"]);
+        assert_eq!(line.spans[0].style, MATCH_STYLE);
+        assert_eq!(line.spans[1].style, Style::default());
+    }
+}
@@ -0,0 +1,101 @@
+use std::cmp::Ordering;
+use strum::Display;
+use crate::metric::Metric;
+
+// the columns of every grouping's table
+#[derive(Clone, Copy, Debug, Default, Display, PartialEq)]
+pub enum Sort {
+    Artist,
+    Album,
+    Title,
+    #[default]
+    Plays,
+    Date,
+    Player,
+    Device,
+    #[strum(to_string = "First heard")]
+    FirstHeard,
+}
+
+// true when descending
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct SortDirection(pub Sort, pub bool);
+
+// rows that can be ordered by the columns of their grouping's table, Plays by metric where it applies
+pub trait SortRow {
+    fn cmp_by(&self, other: &Self, sort: Sort, metric: Metric) -> Ordering;
+}
+
+// the last key in priority is the most significant, rows equal on every key keep their order
+pub fn sort_rows<T: SortRow>(rows: &mut [T], priority: &[SortDirection], metric: Metric) {
+    rows.sort_by(|a, b| {
+        priority.iter()
+            .rev()
+            .fold(Ordering::Equal, |order, SortDirection(sort, descending)| order.then_with(|| {
+                let order = a.cmp_by(b, *sort, metric);
+                if *descending { order.reverse() } else { order }
+            }))
+    });
+}
+
+// makes sort the most significant key, or flips its direction when it already is
+pub fn promote(priority: &mut Vec<SortDirection>, sort: Sort) {
+    let Some(i) = priority.iter().position(|key| key.0 == sort) else {
+        return;
+    };
+
+    let mut key = priority.remove(i);
+    if i == priority.len() {
+        key.1 = !key.1;
+    }
+    priority.push(key);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct Song(&'static str, &'static str, u32);
+
+    impl SortRow for Song {
+        fn cmp_by(&self, other: &Self, sort: Sort, _metric: Metric) -> Ordering {
+            match sort {
+                Sort::Artist => self.0.cmp(other.0),
+                Sort::Title => self.1.cmp(other.1),
+                Sort::Plays => self.2.cmp(&other.2),
+                _ => Ordering::Equal,
+            }
+        }
+    }
+
+    #[test]
+    fn later_keys_are_more_significant() {
+        let mut songs = vec!(Song("b", "x", 1), Song("a", "y", 2), Song("a", "x", 1), Song("c", "z", 2));
+        sort_rows(&mut songs, &[SortDirection(Sort::Artist, false), SortDirection(Sort::Plays, true)], Metric::Plays);
+
+        assert_eq!(songs, [Song("a", "y", 2), Song("c", "z", 2), Song("a", "x", 1), Song("b", "x", 1)]);
+    }
+
+    #[test]
+    fn ties_keep_their_order() {
+        let mut songs = vec!(Song("b", "x", 1), Song("a", "y", 1), Song("c", "z", 1));
+        sort_rows(&mut songs, &[SortDirection(Sort::Plays, false)], Metric::Plays);
+
+        assert_eq!(songs, [Song("b", "x", 1), Song("a", "y", 1), Song("c", "z", 1)]);
+    }
+
+    #[test]
+    fn promote_moves_then_flips() {
+        let mut priority = vec!(SortDirection(Sort::Artist, false), SortDirection(Sort::Plays, true));
+
+        promote(&mut priority, Sort::Artist);
+        assert_eq!(priority, [SortDirection(Sort::Plays, true), SortDirection(Sort::Artist, false)]);
+
+        promote(&mut priority, Sort::Artist);
+        assert_eq!(priority, [SortDirection(Sort::Plays, true), SortDirection(Sort::Artist, true)]);
+
+        promote(&mut priority, Sort::Title);
+        assert_eq!(priority.len(), 2);
+    }
+}
@@ -0,0 +1,114 @@
+use std::env;
+use ratatui::style::{Color, Modifier, Style};
+use mpressed::config::Config;
+
+// calendar cells from no plays to the busiest day, drawn instead of colours when there are none
+pub const SHADES: [&str; 5] = ["· ", "░ ", "▒ ", "▓ ", "█ "];
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Preset {
+    #[default]
+    Default,
+    HighContrast,
+    NoColor,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Theme {
+    // focused borders, headers and labels
+    pub accent: Style,
+    // the highlighted row of a table or list
+    pub selected: Style,
+    // one per artist in the artists over time chart, which also caps how many can be picked
+    pub series: [Color; 8],
+    // calendar cells from no plays to the busiest day
+    pub levels: [Color; 5],
+    // false when nothing may be coloured
+    pub colour: bool,
+}
+
+impl Theme {
+    pub fn preset(preset: Preset) -> Self {
+        match preset {
+            Preset::Default => Theme {
+                accent: Style::new().fg(Color::Red),
+                selected: Style::new().add_modifier(Modifier::REVERSED).fg(Color::Red),
+                series: [Color::Red, Color::Yellow, Color::Green, Color::Cyan, Color::Magenta, Color::Blue, Color::LightRed, Color::LightGreen],
+                levels: [Color::DarkGray, Color::Indexed(52), Color::Indexed(88), Color::Indexed(160), Color::Indexed(196)],
+                colour: true,
+            },
+            Preset::HighContrast => Theme {
+                accent: Style::new().fg(Color::LightYellow).add_modifier(Modifier::BOLD),
+                selected: Style::new().fg(Color::Black).bg(Color::LightYellow).add_modifier(Modifier::BOLD),
+                series: [Color::LightYellow, Color::LightCyan, Color::LightGreen, Color::LightMagenta, Color::White, Color::LightRed, Color::LightBlue, Color::Yellow],
+                levels: [Color::DarkGray, Color::Indexed(100), Color::Indexed(142), Color::Indexed(184), Color::Indexed(226)],
+                colour: true,
+            },
+            Preset::NoColor => Theme {
+                accent: Style::new().add_modifier(Modifier::BOLD),
+                selected: Style::new().add_modifier(Modifier::REVERSED | Modifier::BOLD),
+                series: [Color::Reset; 8],
+                levels: [Color::Reset; 5],
+                colour: false,
+            },
+        }
+    }
+
+    // NO_COLOR wins over the config, see https://no-color.org
+    pub fn load(config: &Config) -> Self {
+        let no_color = env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+        Self::from_config(config, no_color)
+    }
+
+    // [theme] preset is default, high-contrast or none, accent replaces the preset's red or yellow
+    //
+    // [theme]
+    // preset = high-contrast
+    // accent = cyan
+    pub fn from_config(config: &Config, no_color: bool) -> Self {
+        let preset = match config.get("theme", "preset").map(str::to_lowercase).as_deref() {
+            _ if no_color => Preset::NoColor,
+            Some("high-contrast") => Preset::HighContrast,
+            Some("none") => Preset::NoColor,
+            _ => Preset::Default,
+        };
+
+        let mut theme = Self::preset(preset);
+        if let Some(accent) = config.get("theme", "accent").and_then(|accent| accent.parse::<Color>().ok()) {
+            match preset {
+                Preset::Default => {
+                    theme.accent = theme.accent.fg(accent);
+                    theme.selected = theme.selected.fg(accent);
+                }
+                Preset::HighContrast => {
+                    theme.accent = theme.accent.fg(accent);
+                    theme.selected = theme.selected.bg(accent);
+                }
+                Preset::NoColor => {}
+            }
+        }
+        theme
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn presets_and_accent() {
+        assert_eq!(Theme::from_config(&Config::default(), false), Theme::preset(Preset::Default));
+
+        let theme = Theme::from_config(&Config::parse("[theme]\npreset = High-Contrast\naccent = #00ff00"), false);
+        assert_eq!(theme.accent.fg, Some(Color::Rgb(0, 255, 0)));
+        assert_eq!(theme.selected.bg, Some(Color::Rgb(0, 255, 0)));
+        assert_eq!(theme.series, Theme::preset(Preset::HighContrast).series);
+    }
+
+    #[test]
+    fn no_color_wins() {
+        let theme = Theme::from_config(&Config::parse("[theme]\npreset = high-contrast\naccent = red"), true);
+        assert_eq!(theme, Theme::preset(Preset::NoColor));
+        assert!(!theme.colour);
+    }
+}
@@ -0,0 +1,138 @@
+use std::env;
+use std::fs::read_to_string;
+use std::path::PathBuf;
+use crate::{get_config_dir, parse_duration, MIN_PLAYTIME_MS};
+
+pub const CONFIG_FILE_NAME: &str = "config";
+
+// ini style file, keys may repeat within a section
+//
+// [exclude]
+// title = (?i)podcast
+// domain = youtube.com
+#[derive(Clone, Debug, Default)]
+pub struct Config {
+    sections: Vec<(String, Vec<(String, String)>)>,
+}
+
+impl Config {
+    pub fn load() -> Self {
+        read_to_string(get_config_path())
+            .map(|contents| Self::parse(&contents))
+            .unwrap_or_default()
+    }
+
+    pub fn parse(contents: &str) -> Self {
+        let mut sections: Vec<(String, Vec<(String, String)>)> = vec!((String::new(), vec!()));
+
+        for line in contents.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+
+            if let Some(name) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+                sections.push((name.trim().to_lowercase(), vec!()));
+            } else if let Some((key, value)) = line.split_once('=') {
+                sections.last_mut()
+                    .unwrap()
+                    .1
+                    .push((key.trim().to_lowercase(), value.trim().to_string()));
+            }
+        }
+
+        Self { sections }
+    }
+
+    // all entries of every section with this name, in file order
+    pub fn entries<'a>(&'a self, section: &str) -> impl Iterator<Item = (&'a str, &'a str)> + 'a {
+        let section = section.to_lowercase();
+        self.sections.iter()
+            .filter(move |(name, _)| *name == section)
+            .flat_map(|(_, entries)| entries.iter())
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+    }
+
+    // last value wins
+    pub fn get(&self, section: &str, key: &str) -> Option<&str> {
+        self.entries(section)
+            .filter(|(k, _)| *k == key)
+            .last()
+            .map(|(_, value)| value)
+    }
+}
+
+pub fn get_config_path() -> PathBuf {
+    get_config_dir().join(CONFIG_FILE_NAME)
+}
+
+// [daemon] device, falling back to the hostname so plays from different machines stay apart
+pub fn get_device_name(config: &Config) -> String {
+    if let Some(device) = config.get("daemon", "device") {
+        return device.to_string();
+    }
+
+    read_to_string("/proc/sys/kernel/hostname")
+        .map(|hostname| hostname.trim().to_string())
+        .ok()
+        .or_else(|| env::var("HOSTNAME").ok())
+        .unwrap_or_default()
+}
+
+// [daemon] players, comma separated identities of the players to follow
+pub fn get_players(config: &Config) -> Option<Vec<String>> {
+    config.get("daemon", "players").map(|players| {
+        players.split(',')
+            .map(str::trim)
+            .filter(|player| !player.is_empty())
+            .map(str::to_string)
+            .collect()
+    })
+}
+
+// [daemon] min_playtime, how long a song has to play before it counts
+pub fn get_min_playtime_ms(config: &Config) -> i64 {
+    config.get("daemon", "min_playtime")
+        .and_then(parse_duration)
+        .map(|duration| duration.num_milliseconds())
+        .unwrap_or(MIN_PLAYTIME_MS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_sections() {
+        let config = Config::parse("top = level
+            # a comment
+            [Exclude]
+            title = one
+            ; another comment
+            Title = two
+            not a key
+
+            [daemon]
+            device = desk = top
+            [exclude]
+            domain = example.com");
+
+        assert_eq!(config.get("", "top"), Some("level"));
+        assert_eq!(config.entries("exclude").collect::<Vec<_>>(), [("title", "one"), ("title", "two"), ("domain", "example.com")]);
+        assert_eq!(config.get("EXCLUDE", "title"), Some("two"));
+        assert_eq!(config.get("daemon", "device"), Some("desk = top"));
+        assert_eq!(config.get("daemon", "players"), None);
+    }
+
+    #[test]
+    fn reads_daemon_settings() {
+        let config = Config::parse("[daemon]
+            device = desk
+            players = VLC media player, , mpv
+            min_playtime = 30s");
+
+        assert_eq!(get_device_name(&config), "desk");
+        assert_eq!(get_players(&config), Some(vec!("VLC media player".to_string(), "mpv".to_string())));
+        assert_eq!(get_min_playtime_ms(&config), 30000);
+        assert_eq!(get_min_playtime_ms(&Config::default()), MIN_PLAYTIME_MS);
+    }
+}
@@ -1,49 +1,139 @@
+use std::env;
+use std::fs::read_to_string;
+use std::process::exit;
 use std::thread::sleep;
 use std::time::Duration;
 use chrono::Local;
-use log::{debug};
-use mpris::{Metadata, PlaybackStatus, Player, PlayerFinder};
-use rusqlite::{Connection};
-use mpressed::{get_db_path, SongData, MIN_PLAYTIME_MS};
+use mpris::{PlaybackStatus, Player, PlayerFinder};
+use mpressed::{db, get_db_path, tracker, SongData, Track};
+use mpressed::config::{get_config_path, get_device_name, get_min_playtime_ms, get_players, Config};
+use mpressed::exclude::{get_excluded_log_path, log_excluded, Exclusions};
+use mpressed::stats::Stats;
+use mpressed::now_playing::{clear_now_playing, set_now_playing, NowPlaying};
+use mpressed::tracker::{MprisSource, PlayerState, SystemClock, Tracker};
+use mpressed::parse_duration;
+use mpressed::pause::{is_paused, pause, resume, toggle, PauseState};
 
 const IDENTITIES: [&str; 1] = [
     "VLC media player"
     // "Brave"
 ];
 
+struct Settings {
+    exclusions: Exclusions,
+    device: String,
+    players: Vec<String>,
+    min_playtime_ms: i64,
+}
+
+const USAGE: &str = "Usage: mpressed-daemon [pause [DURATION] | resume | toggle [DURATION] | excluded | merge FILE [DEVICE]]
+  DURATION is a number followed by s, m, h or d (default m), e.g. 30m
+  excluded lists the exclusion rules and every play they have dropped
+  merge adds the songs and plays of another mpressed.db, plays recorded before devices were
+  need the DEVICE they were played on";
+
 fn main() {
     env_logger::init();
 
-    let db = Connection::open(get_db_path()).unwrap();
-
-    db.execute("CREATE TABLE if not exists song_data (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                artist TEXT,
-                album TEXT,
-                title TEXT,
-                UNIQUE(artist, album, title)
-            )", [])
-        .expect("Failed to create song_data table");
-
-    db.execute("CREATE TABLE if not exists song_plays (
-                id INTEGER,
-                date TEXT,
-                plays INTEGER,
-                UNIQUE(id, date)
-            )", [])
-        .expect("Failed to create song_plays table");
-
-    player_loop(&db);
+    let args: Vec<String> = env::args().skip(1).collect();
+    if !args.is_empty() {
+        run_command(&args);
+        return;
+    }
+
+    let stats = Stats::open(get_db_path()).expect("Failed to open database");
+
+    let config = Config::load();
+    let settings = Settings {
+        exclusions: Exclusions::from_config(&config),
+        device: get_device_name(&config),
+        players: get_players(&config).unwrap_or_else(|| IDENTITIES.map(String::from).to_vec()),
+        min_playtime_ms: get_min_playtime_ms(&config),
+    };
+
+    player_loop(&stats, &settings);
 }
 
-fn player_loop(db: &Connection) {
+
+fn run_command(args: &[String]) {
+    match args[0].as_str() {
+        "pause" | "resume" | "toggle" => pause_command(args),
+        "excluded" => excluded_command(),
+        "merge" if args.len() > 1 => merge_command(args),
+        _ => {
+            eprintln!("{}", USAGE);
+            exit(1);
+        }
+    }
+}
+
+fn pause_command(args: &[String]) {
+    let duration = match args.get(1) {
+        Some(arg) => match parse_duration(arg) {
+            Some(duration) => Some(duration),
+            None => {
+                eprintln!("Invalid duration: {}\n{}", arg, USAGE);
+                exit(1);
+            }
+        },
+        None => None,
+    };
+
+    let state = match args[0].as_str() {
+        "pause" => pause(duration),
+        "resume" => resume(),
+        _ => toggle(duration),
+    };
+
+    match state {
+        Ok(PauseState::Recording) => println!("Tracking resumed"),
+        Ok(PauseState::Paused(None)) => println!("Tracking paused"),
+        Ok(PauseState::Paused(Some(until))) => println!("Tracking paused until {}", until.format("%Y-%m-%d %H:%M")),
+        Err(err) => {
+            eprintln!("Failed to change tracking state: {}", err);
+            exit(1);
+        }
+    }
+}
+
+fn merge_command(args: &[String]) {
+    let db = db::open(get_db_path()).expect("Failed to open database");
+
+    let device = get_device_name(&Config::load());
+    match db::merge(&db, &args[1], &device, args.get(2).map(String::as_str)) {
+        Ok(stats) => println!(
+            "Merged {}: {} songs added, {} plays added, {} plays updated",
+            args[1], stats.songs_added, stats.plays_added, stats.plays_updated
+        ),
+        Err(err) => {
+            eprintln!("Failed to merge {}: {}", args[1], err);
+            exit(1);
+        }
+    }
+}
+
+fn excluded_command() {
+    let exclusions = Exclusions::from_config(&Config::load());
+
+    println!("Rules from {}:", get_config_path().display());
+    exclusions.rules().iter().for_each(|rule| println!("  {}", rule));
+
+    let path = get_excluded_log_path();
+    println!("Excluded plays from {}:", path.display());
+    match read_to_string(&path) {
+        Ok(log) => print!("{}", log),
+        Err(_) => println!("  none yet"),
+    }
+}
+
+fn player_loop(stats: &Stats, settings: &Settings) {
     let player_finder: PlayerFinder = PlayerFinder::new().expect("Could not connect to D-Bus");
 
     loop {
-        for identity in IDENTITIES {
+        for identity in &settings.players {
             if player_finder.find_by_name(identity).is_ok() {
                 println!("Showing event stream for player {}", identity);
-                tracker_loop(db, &mut player_finder.find_by_name(identity).unwrap());
+                tracker_loop(stats, settings, &mut player_finder.find_by_name(identity).unwrap());
                 println!("Event stream ended.");
                 break;
             }
@@ -53,89 +143,57 @@ fn player_loop(db: &Connection) {
     }
 }
 
-fn tracker_loop(db: &Connection, player: &mut Player) {
-    let mut written = false;
-    let mut song_option: Option<SongData> = get_song_data(&player.get_metadata().unwrap());
-    let mut song_playtime: i64 = 0;
-    let mut current_date = Local::now().date_naive().to_string();
-
-    let mut player_tracker = player.track_progress(1000)
+fn tracker_loop(stats: &Stats, settings: &Settings, player: &mut Player) {
+    let mut source = MprisSource::new(player)
         .expect("Failed to start progress tracker");
 
-    loop {
-        debug!("tick: {}, {:?}", song_playtime, song_option);
-
-        let last_tick = Local::now().timestamp_millis();
-        let tick = player_tracker.tick();
-
-        if let Ok(status) = player.get_playback_status() {
-            if status != PlaybackStatus::Playing {
-                continue;
-            }
-        } else {
-            break;
+    let on_tick = |tracker: &Tracker, state: &PlayerState| {
+        let Some(track) = &state.track else {
+            let _ = clear_now_playing();
+            return;
+        };
+
+        let now_playing = NowPlaying {
+            updated: Local::now(),
+            player: track.player.clone(),
+            playing: state.status == PlaybackStatus::Playing,
+            song: track.song.clone(),
+            playtime_ms: tracker.playtime(),
+            min_playtime_ms: settings.min_playtime_ms,
+            counted: tracker.written(),
+            paused: is_paused(),
+            excluded: settings.exclusions.check(track).map(|rule| rule.to_string()),
+        };
+        if let Err(err) = set_now_playing(&now_playing) {
+            println!("Failed to write now playing: {}", err);
         }
-
-        let song_current = get_song_data(tick.progress.metadata());
-
-        if song_option.is_some() && song_current.is_some() && song_current.clone().unwrap() == song_option.clone().unwrap() {
-            song_playtime += Local::now().timestamp_millis() - last_tick;
-            if !written && song_playtime >= MIN_PLAYTIME_MS {
-                write(db, &song_current.unwrap(), &current_date);
-                written = true;
-            }
-            continue;
+    };
+
+    tracker::run(&mut source, &SystemClock, settings.min_playtime_ms, on_tick, |play| {
+        let song = &play.track.song;
+        if is_paused() {
+            println!("Tracking paused, skipped: {:?}", (&song.artist, &song.album, &song.title));
+        } else if let Some(rule) = settings.exclusions.check(&play.track) {
+            println!("Excluded by {}: {:?}", rule, (&song.artist, &song.album, &song.title));
+            log_excluded(&play.track, rule);
+        } else {
+            write(stats, &play.track, &play.date, &settings.device);
         }
+    });
 
-        written = false;
-        song_option = song_current;
-        song_playtime = 0;
-        current_date = Local::now().date_naive().to_string();
-    }
+    // the player went away
+    let _ = clear_now_playing();
 }
 
-fn get_song_data(data: &Metadata) -> Option<SongData> {
-    Some(SongData {
-        // ISSUE
-        // opus only allows for one artist and joins by ","
-        // other formats join by " / "
-        artist: data.artists()?.join(" / "),
-        album: data.album_name()?.to_string(),
-        title: data.title()?.to_string(),
-    })
-}
-
-fn write(db: &Connection, song: &SongData, current_date: &str) {
+fn write(stats: &Stats, track: &Track, current_date: &str, device: &str) {
+    let song = &track.song;
     if *song == SongData::default() {
         return;
     }
 
-    db.execute("INSERT OR IGNORE INTO song_data (artist, album, title) VALUES (?1, ?2, ?3)",
-               (&song.artist, &song.album, &song.title))
-        .expect(&format!("Failed to inserted song_data: {:?}", (&song.artist, &song.album, &song.title)));
-
-    let id: u32 = db.prepare("SELECT ID FROM song_data WHERE artist = (?1) AND album = (?2) AND title = (?3) LIMIT 1")
-        .unwrap()
-        .query((&song.artist, &song.album, &song.title))
-        .unwrap()
-        .next()
-        .unwrap()
-        .unwrap()
-        .get(0)
-        .unwrap();
-
-    let update = db.prepare("UPDATE song_plays SET plays = plays + 1 WHERE id = (?1) AND date = (?2)")
-        .unwrap()
-        .execute((id, &current_date))
-        .expect(&format!("Failed to update song_plays: {:?}", (&song.artist, &song.album, &song.title)));
-
-    if update as u32 == 1 {
-        println!("Updated song_plays: {:?}", (&song.artist, &song.album, &song.title));
-    } else {
-        match db.execute("INSERT INTO song_plays (id, date, plays) VALUES (?1, ?2, ?3)",
-                         (id, &current_date, 1)) {
-            Ok(_) => println!("Inserted song_plays: {:?}", (&song.artist, &song.album, &song.title)),
-            Err(_) => println!("Failed to insert song_plays: {:?}", (&song.artist, &song.album, &song.title)),
-        }
+    match stats.record_play(song, current_date, &track.player, device, track.length) {
+        Ok(true) => println!("Inserted song_plays: {:?}", (&song.artist, &song.album, &song.title)),
+        Ok(false) => println!("Updated song_plays: {:?}", (&song.artist, &song.album, &song.title)),
+        Err(err) => println!("Failed to write song_plays: {:?}: {}", (&song.artist, &song.album, &song.title), err),
     }
 }
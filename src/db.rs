@@ -0,0 +1,475 @@
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+use rusqlite::types::Value;
+use rusqlite::{params_from_iter, Connection, OpenFlags, Transaction, TransactionBehavior};
+use crate::config::{get_device_name, Config};
+
+// bumped whenever a migration is added to migrate()
+pub const SCHEMA_VERSION: u32 = 5;
+
+// what an album is keyed on besides its title, the album artist if the player
+// reported one, otherwise the first of the song's artists. table is the alias of song_data
+pub fn album_artist(table: &str) -> String {
+    format!("COALESCE(NULLIF({table}.album_artist, ''),
+        CASE WHEN instr({table}.artist, ' / ') > 0 THEN substr({table}.artist, 1, instr({table}.artist, ' / ') - 1) ELSE {table}.artist END)")
+}
+
+pub fn open<P: AsRef<Path>>(path: P) -> rusqlite::Result<Connection> {
+    let db = Connection::open(path)?;
+    init(&db)?;
+    Ok(db)
+}
+
+pub fn init(db: &Connection) -> rusqlite::Result<()> {
+    init_as(db, || get_device_name(&Config::load()))
+}
+
+// local_device names this machine, it's only asked for when there are plays without a device to give it to
+pub fn init_as(db: &Connection, local_device: impl FnOnce() -> String) -> rusqlite::Result<()> {
+    if user_version(db)? >= SCHEMA_VERSION {
+        return Ok(());
+    }
+
+    // the migrations and the version bump happen together or not at all, immediate so
+    // another process opening the file at the same time waits rather than migrating it too
+    let db = Transaction::new_unchecked(db, TransactionBehavior::Immediate)?;
+    let version = user_version(&db)?;
+    if version < SCHEMA_VERSION {
+        migrate(&db, version, local_device)?;
+    }
+
+    db.execute("CREATE TABLE if not exists song_data (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                artist TEXT,
+                album TEXT,
+                title TEXT,
+                album_artist TEXT NOT NULL DEFAULT '',
+                length INTEGER,
+                UNIQUE(artist, album, title)
+            )", [])?;
+
+    db.execute("CREATE TABLE if not exists song_plays (
+                id INTEGER,
+                date TEXT,
+                plays INTEGER,
+                player TEXT NOT NULL DEFAULT '',
+                device TEXT NOT NULL DEFAULT '',
+                UNIQUE(id, date, player, device)
+            )", [])?;
+
+    // the counts merge() has taken from other files, so only what they gained since is added again
+    db.execute("CREATE TABLE if not exists merged_plays (
+                id INTEGER,
+                date TEXT,
+                plays INTEGER,
+                player TEXT NOT NULL DEFAULT '',
+                device TEXT NOT NULL DEFAULT '',
+                UNIQUE(id, date, player, device)
+            )", [])?;
+
+    db.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+    db.commit()
+}
+
+fn user_version(db: &Connection) -> rusqlite::Result<u32> {
+    db.query_row("PRAGMA user_version", [], |row| row.get(0))
+}
+
+// brings tables created by older versions up to date, tables that don't exist yet are left to init()
+fn migrate(db: &Connection, version: u32, local_device: impl FnOnce() -> String) -> rusqlite::Result<()> {
+    let local_device = match version < 5 && table_exists(db, "song_plays")? {
+        true => local_device(),
+        false => String::new(),
+    };
+
+    if version < 1 && table_exists(db, "song_plays")? {
+        // sqlite can't alter a UNIQUE constraint so the table is rebuilt
+        db.execute_batch("ALTER TABLE song_plays RENAME TO song_plays_v0;
+            CREATE TABLE song_plays (
+                id INTEGER,
+                date TEXT,
+                plays INTEGER,
+                player TEXT NOT NULL DEFAULT '',
+                device TEXT NOT NULL DEFAULT '',
+                UNIQUE(id, date, player, device)
+            );
+            INSERT INTO song_plays (id, date, plays) SELECT id, date, plays FROM song_plays_v0;
+            DROP TABLE song_plays_v0;")?;
+    }
+
+    if version < 2 && table_exists(db, "song_data")? {
+        db.execute("ALTER TABLE song_data ADD COLUMN album_artist TEXT NOT NULL DEFAULT ''", [])?;
+    }
+
+    // milliseconds, NULL when the player never reported one
+    if version < 3 && table_exists(db, "song_data")? {
+        db.execute("ALTER TABLE song_data ADD COLUMN length INTEGER", [])?;
+    }
+
+    // plays recorded before devices were are this machine's, left without one they
+    // would be taken for the same plays as another machine's legacy ones when merging
+    if version < 4 && table_exists(db, "song_plays")? {
+        db.execute("UPDATE song_plays SET device = ?1 WHERE device = ''", [&local_device])?;
+    }
+
+    // other machines' plays could only have come from merging, at the larger count merges used to keep
+    if version < 5 && table_exists(db, "song_plays")? {
+        db.execute("CREATE TABLE merged_plays (
+                id INTEGER,
+                date TEXT,
+                plays INTEGER,
+                player TEXT NOT NULL DEFAULT '',
+                device TEXT NOT NULL DEFAULT '',
+                UNIQUE(id, date, player, device)
+            )", [])?;
+        db.execute("INSERT INTO merged_plays SELECT id, date, plays, player, device FROM song_plays WHERE device != ?1", [&local_device])?;
+    }
+
+    Ok(())
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MergeStats {
+    pub songs_added: usize,
+    pub plays_added: usize,
+    pub plays_updated: usize,
+}
+
+#[derive(Debug)]
+pub enum MergeError {
+    Sqlite(rusqlite::Error),
+    // the other file has plays recorded before devices were, and no device was given for them
+    DeviceNeeded,
+}
+
+impl From<rusqlite::Error> for MergeError {
+    fn from(err: rusqlite::Error) -> Self {
+        MergeError::Sqlite(err)
+    }
+}
+
+impl Display for MergeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MergeError::Sqlite(err) => write!(f, "{}", err),
+            MergeError::DeviceNeeded => write!(f, "it has plays without a device, name the device they were played on"),
+        }
+    }
+}
+
+// songs are matched on (artist, album, title) and get the ids of this database,
+// plays are keyed on (song, date, player, device) and their counts added. what was
+// added from any file before is remembered in merged_plays, so merging the same file
+// again, or one that merged it, only adds what was played since. plays recorded on
+// local_device are already here and are skipped. rows without a device in the other
+// file are given unknown_device, which they need
+pub fn merge<P: AsRef<Path>>(db: &Connection, other: P, local_device: &str, unknown_device: Option<&str>) -> Result<MergeStats, MergeError> {
+    // never created or migrated, so a mistyped path is an error and the file is left as it was
+    let source = Connection::open_with_flags(other, OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX)?;
+
+    let tx = db.unchecked_transaction()?;
+    copy_other(&source, &tx)?;
+
+    let without_device: usize = tx.query_row("SELECT COUNT(*) FROM temp.other_song_plays WHERE device = ''", [], |row| row.get(0))?;
+    let unknown_device = match unknown_device {
+        Some(device) => device,
+        None if without_device == 0 => "",
+        None => return Err(MergeError::DeviceNeeded),
+    };
+
+    let stats = merge_copied(&tx, local_device, unknown_device)?;
+    tx.execute_batch("DROP TABLE temp.other_song_data; DROP TABLE temp.other_song_plays;")?;
+    tx.commit()?;
+
+    Ok(stats)
+}
+
+// the other file's tables as temporary ones in the current layout, whichever version wrote it
+fn copy_other(source: &Connection, db: &Connection) -> rusqlite::Result<()> {
+    db.execute_batch("CREATE TEMP TABLE other_song_data (id INTEGER, artist TEXT, album TEXT, title TEXT, album_artist TEXT, length INTEGER);
+        CREATE TEMP TABLE other_song_plays (id INTEGER, date TEXT, plays INTEGER, player TEXT, device TEXT);")?;
+
+    let or = |table: &str, column: &'static str, missing: &'static str| -> rusqlite::Result<&'static str> {
+        Ok(if has_column(source, table, column)? { column } else { missing })
+    };
+
+    let songs = format!("SELECT id, artist, album, title, {}, {} FROM song_data",
+                        or("song_data", "album_artist", "''")?, or("song_data", "length", "NULL")?);
+    copy_rows(source, &songs, db, "INSERT INTO temp.other_song_data VALUES (?1, ?2, ?3, ?4, ?5, ?6)")?;
+
+    let plays = format!("SELECT id, date, plays, {}, {} FROM song_plays",
+                        or("song_plays", "player", "''")?, or("song_plays", "device", "''")?);
+    copy_rows(source, &plays, db, "INSERT INTO temp.other_song_plays VALUES (?1, ?2, ?3, ?4, ?5)")
+}
+
+fn copy_rows(source: &Connection, select: &str, db: &Connection, insert: &str) -> rusqlite::Result<()> {
+    let mut select = source.prepare(select)?;
+    let mut insert = db.prepare(insert)?;
+    let columns = select.column_count();
+
+    let mut rows = select.query([])?;
+    while let Some(row) = rows.next()? {
+        let values = (0..columns)
+            .map(|column| row.get::<usize, Value>(column))
+            .collect::<rusqlite::Result<Vec<Value>>>()?;
+        insert.execute(params_from_iter(values))?;
+    }
+    Ok(())
+}
+
+fn merge_copied(db: &Connection, local_device: &str, unknown_device: &str) -> rusqlite::Result<MergeStats> {
+    let songs_added = db.execute("INSERT OR IGNORE INTO main.song_data (artist, album, title, album_artist, length)
+            SELECT artist, album, title, album_artist, length FROM temp.other_song_data ORDER BY id", [])?;
+
+    // songs recorded before album artists were stored pick them up from the other file
+    db.execute("UPDATE main.song_data SET album_artist = (
+                SELECT song.album_artist FROM temp.other_song_data AS song
+                WHERE song.artist = song_data.artist AND song.album = song_data.album AND song.title = song_data.title
+            )
+            WHERE album_artist = '' AND EXISTS (
+                SELECT 1 FROM temp.other_song_data AS song
+                WHERE song.artist = song_data.artist AND song.album = song_data.album AND song.title = song_data.title AND song.album_artist != ''
+            )", [])?;
+
+    // as do songs recorded before lengths were
+    db.execute("UPDATE main.song_data SET length = (
+                SELECT song.length FROM temp.other_song_data AS song
+                WHERE song.artist = song_data.artist AND song.album = song_data.album AND song.title = song_data.title
+            )
+            WHERE length IS NULL", [])?;
+
+    // the other file's plays with this database's ids, less what earlier merges took of them
+    db.execute_batch("CREATE TEMP TABLE incoming_plays (id INTEGER, date TEXT, plays INTEGER, player TEXT, device TEXT)")?;
+    db.execute("INSERT INTO temp.incoming_plays
+            SELECT local.id, plays.date, plays.plays, plays.player, CASE plays.device WHEN '' THEN ?1 ELSE plays.device END
+            FROM temp.other_song_plays AS plays
+            JOIN temp.other_song_data AS song ON plays.id = song.id
+            JOIN main.song_data AS local ON local.artist = song.artist AND local.album = song.album AND local.title = song.title", [unknown_device])?;
+    db.execute("DELETE FROM temp.incoming_plays WHERE device = ?1", [local_device])?;
+
+    let plays_before: usize = db.query_row("SELECT COUNT(*) FROM main.song_plays", [], |row| row.get(0))?;
+
+    let plays_changed = db.execute("INSERT INTO main.song_plays (id, date, plays, player, device)
+            SELECT incoming.id, incoming.date, incoming.plays - COALESCE(merged.plays, 0), incoming.player, incoming.device
+            FROM temp.incoming_plays AS incoming
+            LEFT JOIN main.merged_plays AS merged USING (id, date, player, device)
+            WHERE incoming.plays > COALESCE(merged.plays, 0)
+            ON CONFLICT (id, date, player, device) DO UPDATE SET plays = song_plays.plays + excluded.plays", [])?;
+
+    let plays_after: usize = db.query_row("SELECT COUNT(*) FROM main.song_plays", [], |row| row.get(0))?;
+
+    db.execute("INSERT INTO main.merged_plays (id, date, plays, player, device)
+            SELECT id, date, plays, player, device FROM temp.incoming_plays WHERE true
+            ON CONFLICT (id, date, player, device) DO UPDATE SET plays = excluded.plays
+            WHERE excluded.plays > merged_plays.plays", [])?;
+    db.execute_batch("DROP TABLE temp.incoming_plays")?;
+
+    Ok(MergeStats {
+        songs_added,
+        plays_added: plays_after - plays_before,
+        plays_updated: plays_changed - (plays_after - plays_before),
+    })
+}
+
+fn table_exists(db: &Connection, name: &str) -> rusqlite::Result<bool> {
+    db.query_row("SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = ?1", [name], |row| row.get::<usize, u32>(0))
+        .map(|count| count > 0)
+}
+
+fn has_column(db: &Connection, table: &str, column: &str) -> rusqlite::Result<bool> {
+    db.query_row("SELECT COUNT(*) FROM pragma_table_info(?1) WHERE name = ?2", [table, column], |row| row.get::<usize, u32>(0))
+        .map(|count| count > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env::temp_dir;
+    use std::fs::{create_dir_all, remove_file};
+    use std::path::PathBuf;
+    use std::process;
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        let dir = temp_dir().join(format!("mpressed-db-{}", process::id()));
+        create_dir_all(&dir).unwrap();
+        let path = dir.join(format!("{}.db", name));
+        let _ = remove_file(&path);
+        path
+    }
+
+    // the tables as they were before user_version was set
+    fn legacy(db: &Connection, plays: &[(&str, &str, u32)]) {
+        db.execute_batch("CREATE TABLE song_data (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                artist TEXT,
+                album TEXT,
+                title TEXT,
+                UNIQUE(artist, album, title)
+            );
+            CREATE TABLE song_plays (
+                id INTEGER,
+                date TEXT,
+                plays INTEGER,
+                UNIQUE(id, date)
+            );").unwrap();
+
+        for (title, date, count) in plays {
+            db.execute("INSERT OR IGNORE INTO song_data (artist, album, title) VALUES ('Artist', 'Album', ?1)", [title]).unwrap();
+            db.execute("INSERT INTO song_plays (id, date, plays) SELECT id, ?2, ?3 FROM song_data WHERE title = ?1", (title, date, count)).unwrap();
+        }
+    }
+
+    fn legacy_file(name: &str, plays: &[(&str, &str, u32)]) -> PathBuf {
+        let path = temp_path(name);
+        legacy(&Connection::open(&path).unwrap(), plays);
+        path
+    }
+
+    fn plays(db: &Connection) -> Vec<(String, String, u32, String, String)> {
+        db.prepare("SELECT title, date, plays, player, device FROM song_plays JOIN song_data USING (id) ORDER BY title, date, device")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?)))
+            .unwrap()
+            .map(|row| row.unwrap())
+            .collect()
+    }
+
+    fn total(db: &Connection) -> u32 {
+        db.query_row("SELECT SUM(plays) FROM song_plays", [], |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn migrates_legacy_tables() {
+        let db = Connection::open_in_memory().unwrap();
+        legacy(&db, &[("Song", "2024-01-01", 3)]);
+        init_as(&db, || "desk".to_string()).unwrap();
+
+        assert_eq!(user_version(&db).unwrap(), SCHEMA_VERSION);
+        assert_eq!(plays(&db), [("Song".to_string(), "2024-01-01".to_string(), 3, String::new(), "desk".to_string())]);
+
+        let (album_artist, length): (String, Option<i64>) = db
+            .query_row("SELECT album_artist, length FROM song_data", [], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap();
+        assert_eq!((album_artist.as_str(), length), ("", None));
+
+        // the device of this machine is only looked up when there is something to migrate
+        init_as(&db, || panic!("migrated twice")).unwrap();
+        init_as(&Connection::open_in_memory().unwrap(), || panic!("nothing to migrate")).unwrap();
+    }
+
+    #[test]
+    fn failed_migrations_change_nothing() {
+        let db = Connection::open_in_memory().unwrap();
+        legacy(&db, &[("Song", "2024-01-01", 3)]);
+        // makes adding the length column fail after the earlier steps have run
+        db.execute("ALTER TABLE song_data ADD COLUMN length INTEGER", []).unwrap();
+
+        assert!(init_as(&db, || "desk".to_string()).is_err());
+        assert_eq!(user_version(&db).unwrap(), 0);
+        assert!(!has_column(&db, "song_data", "album_artist").unwrap());
+        assert!(!has_column(&db, "song_plays", "device").unwrap());
+        assert_eq!(total(&db), 3);
+    }
+
+    #[test]
+    fn merging_legacy_files_adds_their_plays() {
+        let db = Connection::open_in_memory().unwrap();
+        legacy(&db, &[("Song", "2024-01-01", 3)]);
+        init_as(&db, || "desk".to_string()).unwrap();
+
+        let other = legacy_file("legacy", &[("Song", "2024-01-01", 2), ("Other", "2024-01-02", 1)]);
+        assert!(matches!(merge(&db, &other, "desk", None), Err(MergeError::DeviceNeeded)));
+        assert_eq!(total(&db), 3);
+
+        let stats = merge(&db, &other, "desk", Some("laptop")).unwrap();
+        assert_eq!(stats, MergeStats { songs_added: 1, plays_added: 2, plays_updated: 0 });
+        assert_eq!(total(&db), 6);
+        let devices: Vec<String> = plays(&db).into_iter().map(|play| play.4).collect();
+        assert_eq!(devices, ["laptop", "desk", "laptop"]);
+
+        // the other file is read as it is, not migrated
+        assert_eq!(user_version(&Connection::open(&other).unwrap()).unwrap(), 0);
+        remove_file(other).unwrap();
+    }
+
+    #[test]
+    fn merging_twice_changes_nothing() {
+        let db = Connection::open_in_memory().unwrap();
+        init_as(&db, String::new).unwrap();
+
+        let path = temp_path("twice");
+        let other = open(&path).unwrap();
+        other.execute("INSERT INTO song_data (artist, album, title) VALUES ('Artist', 'Album', 'Song')", []).unwrap();
+        other.execute("INSERT INTO song_plays (id, date, plays, player, device) VALUES (1, '2024-01-01', 4, 'mpv', 'laptop')", []).unwrap();
+
+        assert_eq!(merge(&db, &path, "desk", Some("unused")).unwrap(), MergeStats { songs_added: 1, plays_added: 1, plays_updated: 0 });
+        assert_eq!(merge(&db, &path, "desk", Some("unused")).unwrap(), MergeStats::default());
+        assert_eq!(plays(&db), [("Song".to_string(), "2024-01-01".to_string(), 4, "mpv".to_string(), "laptop".to_string())]);
+
+        // the other machine played it again since, only that play is added
+        other.execute("UPDATE song_plays SET plays = 5", []).unwrap();
+        assert_eq!(merge(&db, &path, "desk", None).unwrap(), MergeStats { songs_added: 0, plays_added: 0, plays_updated: 1 });
+        assert_eq!(total(&db), 5);
+
+        drop(other);
+        remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn merging_adds_counts_without_counting_anything_twice() {
+        let db = Connection::open_in_memory().unwrap();
+        init_as(&db, String::new).unwrap();
+        db.execute("INSERT INTO song_data (artist, album, title) VALUES ('Artist', 'Album', 'Song')", []).unwrap();
+        db.execute("INSERT INTO song_plays (id, date, plays, player, device) VALUES (1, '2024-01-01', 3, 'mpv', 'desk')", []).unwrap();
+        // a machine that shares its device name with another one
+        db.execute("INSERT INTO song_plays (id, date, plays, player, device) VALUES (1, '2024-01-01', 1, 'mpv', 'shared')", []).unwrap();
+
+        // the laptop merged the desk's file before, and has plays of its own
+        let path = temp_path("relay");
+        let other = open(&path).unwrap();
+        other.execute("INSERT INTO song_data (artist, album, title) VALUES ('Artist', 'Album', 'Song')", []).unwrap();
+        other.execute_batch("INSERT INTO song_plays (id, date, plays, player, device) VALUES
+            (1, '2024-01-01', 3, 'mpv', 'desk'),
+            (1, '2024-01-01', 2, 'mpv', 'laptop'),
+            (1, '2024-01-01', 2, 'mpv', 'shared')").unwrap();
+        drop(other);
+
+        assert_eq!(merge(&db, &path, "desk", None).unwrap(), MergeStats { songs_added: 0, plays_added: 1, plays_updated: 1 });
+        assert_eq!(merge(&db, &path, "desk", None).unwrap(), MergeStats::default());
+
+        let counts: Vec<(u32, String)> = plays(&db).into_iter().map(|play| (play.2, play.4)).collect();
+        assert_eq!(counts, [(3, "desk".to_string()), (2, "laptop".to_string()), (3, "shared".to_string())]);
+
+        remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn merging_fills_in_album_artists_and_lengths() {
+        let db = Connection::open_in_memory().unwrap();
+        init_as(&db, String::new).unwrap();
+        db.execute("INSERT INTO song_data (artist, album, title) VALUES ('Artist', 'Album', 'Song')", []).unwrap();
+
+        let path = temp_path("album-artist");
+        let other = open(&path).unwrap();
+        other.execute("INSERT INTO song_data (artist, album, title, album_artist, length) VALUES ('Artist', 'Album', 'Song', 'Various', 180000)", []).unwrap();
+        drop(other);
+
+        assert_eq!(merge(&db, &path, "desk", None).unwrap(), MergeStats::default());
+        let (album_artist, length): (String, Option<i64>) = db
+            .query_row("SELECT album_artist, length FROM song_data", [], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap();
+        assert_eq!((album_artist.as_str(), length), ("Various", Some(180000)));
+
+        remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn merging_a_missing_file_fails() {
+        let db = Connection::open_in_memory().unwrap();
+        init_as(&db, String::new).unwrap();
+
+        let path = temp_path("missing");
+        assert!(merge(&db, &path, "desk", None).is_err());
+        assert!(!path.exists());
+    }
+}
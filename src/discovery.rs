@@ -0,0 +1,145 @@
+use std::collections::BTreeMap;
+use chrono::NaiveDate;
+use crate::SongData;
+use crate::period::Period;
+use crate::stats::{AlbumFirstPlay, ArtistFirstPlay, Filter, SongFirstPlay, Stats};
+
+// what was played for the first time ever in one period, each list first discovered first
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Discoveries {
+    pub start: NaiveDate,
+    pub label: String,
+    pub artists: Vec<String>,
+    // album artist and album
+    pub albums: Vec<(String, String)>,
+    pub songs: Vec<SongData>,
+}
+
+// the first play ever of every artist, album and song played matching a filter
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FirstPlays {
+    pub artists: Vec<ArtistFirstPlay>,
+    pub albums: Vec<AlbumFirstPlay>,
+    pub songs: Vec<SongFirstPlay>,
+}
+
+impl FirstPlays {
+    pub fn load(stats: &Stats, filter: &Filter) -> rusqlite::Result<Self> {
+        let filter = filter.clone().limit(None);
+
+        Ok(Self {
+            artists: stats.artist_first_plays(&filter)?,
+            albums: stats.album_first_plays(&filter)?,
+            songs: stats.song_first_plays(&filter)?,
+        })
+    }
+}
+
+// every period in date order, with an empty one for each period nothing new was played in
+// between from and to, or between the first and last discovery when they are None,
+// anything first played before the range isn't a discovery in it
+pub fn discoveries(first_plays: &FirstPlays, filter: &Filter, period: Period) -> Vec<Discoveries> {
+    let mut periods: BTreeMap<NaiveDate, Discoveries> = BTreeMap::new();
+
+    for artist in &first_plays.artists {
+        if let Some(discoveries) = period_of(&mut periods, filter, period, &artist.first_played) {
+            discoveries.artists.push(artist.artist.clone());
+        }
+    }
+    for album in &first_plays.albums {
+        if let Some(discoveries) = period_of(&mut periods, filter, period, &album.first_played) {
+            discoveries.albums.push((album.artist.clone(), album.album.clone()));
+        }
+    }
+    for song in &first_plays.songs {
+        if let Some(discoveries) = period_of(&mut periods, filter, period, &song.first_played) {
+            discoveries.songs.push(song.song.clone());
+        }
+    }
+
+    let (Some(first), Some(last)) = (
+        filter.from.map(|from| period.start(from)).or(periods.keys().next().copied()),
+        filter.to.map(|to| period.start(to)).or(periods.keys().next_back().copied()),
+    ) else {
+        return vec!();
+    };
+
+    let mut filled = vec!();
+    let mut start = first;
+    while start <= last {
+        filled.push(periods.remove(&start).unwrap_or_else(|| Discoveries {
+            start,
+            label: period.label(start),
+            ..Discoveries::default()
+        }));
+        start = period.after(start);
+    }
+    filled
+}
+
+// None when the date is before the filter's range
+fn period_of<'a>(periods: &'a mut BTreeMap<NaiveDate, Discoveries>, filter: &Filter, period: Period, first_played: &str) -> Option<&'a mut Discoveries> {
+    let date = first_played.parse::<NaiveDate>().ok()
+        .filter(|date| filter.from.is_none_or(|from| *date >= from))?;
+    let start = period.start(date);
+
+    Some(periods.entry(start).or_insert_with(|| Discoveries {
+        start,
+        label: period.label(start),
+        ..Discoveries::default()
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn song(artist: &str, album: &str, title: &str) -> SongData {
+        SongData {
+            artist: artist.to_string(),
+            album: album.to_string(),
+            title: title.to_string(),
+            ..SongData::default()
+        }
+    }
+
+    fn date(text: &str) -> Option<NaiveDate> {
+        text.parse().ok()
+    }
+
+    // Old is first played in 2023, New in the second week of 2024 along with a second album by Old
+    fn stats() -> Stats {
+        let stats = Stats::open_in_memory().unwrap();
+        stats.record_play(&song("Old", "First", "a"), "2023-12-31", "VLC", "desktop", None).unwrap();
+        stats.record_play(&song("Old", "First", "a"), "2024-01-01", "VLC", "desktop", None).unwrap();
+        stats.record_play(&song("Old", "First", "b"), "2024-01-02", "VLC", "desktop", None).unwrap();
+        stats.record_play(&song("New", "Debut", "c"), "2024-01-09", "VLC", "desktop", None).unwrap();
+        stats.record_play(&song("Old", "Second", "d"), "2024-01-10", "VLC", "desktop", None).unwrap();
+        stats
+    }
+
+    fn counts(discoveries: &[Discoveries]) -> Vec<(&str, usize, usize, usize)> {
+        discoveries.iter()
+            .map(|found| (found.label.as_str(), found.artists.len(), found.albums.len(), found.songs.len()))
+            .collect()
+    }
+
+    #[test]
+    fn per_week_in_the_range() {
+        let filter = Filter::default().range(date("2024-01-01"), date("2024-01-21"));
+        let discoveries = discoveries(&FirstPlays::load(&stats(), &filter).unwrap(), &filter, Period::Week);
+
+        assert_eq!(counts(&discoveries), [("2024-W01", 0, 0, 1), ("2024-W02", 1, 2, 2), ("2024-W03", 0, 0, 0)]);
+        assert_eq!(discoveries[1].albums, [("New".to_string(), "Debut".to_string()), ("Old".to_string(), "Second".to_string())]);
+        assert_eq!(discoveries[0].songs, [song("Old", "First", "b")]);
+    }
+
+    #[test]
+    fn all_time_starts_with_the_first_play() {
+        let filter = Filter::default();
+        let discoveries = discoveries(&FirstPlays::load(&stats(), &filter).unwrap(), &filter, Period::Month);
+
+        assert_eq!(counts(&discoveries), [("2023-12", 1, 1, 1), ("2024-01", 1, 2, 3)]);
+        assert!(super::discoveries(&FirstPlays::default(), &filter, Period::Month).is_empty());
+    }
+}
@@ -0,0 +1,307 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::env;
+use std::ffi::CString;
+use std::process::exit;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+use dbus::arg::{PropMap, RefArg, Variant};
+use dbus::blocking::LocalConnection;
+use dbus::blocking::stdintf::org_freedesktop_dbus::PropertiesPropertiesChanged;
+use dbus::channel::{MatchingReceiver, Sender};
+use dbus::message::{MatchRule, MessageType, SignalArgs};
+use dbus::strings::ErrorName;
+use dbus::{Message, Path};
+
+// stands in for a real player on a private session bus in the daemon's integration tests
+
+const USAGE: &str = "Usage: mpressed-fake-player [--identity NAME] STEP...
+  play ARTIST ALBUM TITLE SECONDS  plays a track, continuing it if it is already the current one
+  replay SECONDS                   starts the current track over
+  pause SECONDS                    pauses the current track
+  url URL                          sets xesam:url for the following tracks
+  length SECONDS                   sets mpris:length for the following tracks
+The player quits once every step has run";
+
+const OBJECT_PATH: &str = "/org/mpris/MediaPlayer2";
+const ROOT_INTERFACE: &str = "org.mpris.MediaPlayer2";
+const PLAYER_INTERFACE: &str = "org.mpris.MediaPlayer2.Player";
+
+const INTROSPECTION: &str = r#"<!DOCTYPE node PUBLIC "-//freedesktop//DTD D-BUS Object Introspection 1.0//EN"
+ "http://www.freedesktop.org/standards/dbus/1.0/introspect.dtd">
+<node>
+  <interface name="org.freedesktop.DBus.Properties"/>
+  <interface name="org.freedesktop.DBus.Introspectable"/>
+  <interface name="org.mpris.MediaPlayer2"/>
+  <interface name="org.mpris.MediaPlayer2.Player"/>
+</node>"#;
+
+#[derive(Clone, Debug, Default)]
+struct FakeTrack {
+    artist: String,
+    album: String,
+    title: String,
+    url: Option<String>,
+    length: Option<Duration>,
+}
+
+#[derive(Debug)]
+enum Step {
+    Play(FakeTrack, u64),
+    Replay(u64),
+    Pause(u64),
+}
+
+#[derive(Debug)]
+struct FakeState {
+    identity: String,
+    playing: bool,
+    track: Option<FakeTrack>,
+    track_number: u32,
+    // position when playback last started or stopped
+    position: Duration,
+    since: Instant,
+}
+
+impl FakeState {
+    fn position(&self) -> Duration {
+        if self.playing {
+            self.position + self.since.elapsed()
+        } else {
+            self.position
+        }
+    }
+
+    fn set_playing(&mut self, playing: bool) {
+        self.position = self.position();
+        self.since = Instant::now();
+        self.playing = playing;
+    }
+
+    fn restart(&mut self) {
+        self.position = Duration::ZERO;
+        self.since = Instant::now();
+    }
+
+    fn playback_status(&self) -> &'static str {
+        match (self.playing, &self.track) {
+            (_, None) => "Stopped",
+            (true, _) => "Playing",
+            (false, _) => "Paused",
+        }
+    }
+
+    fn metadata(&self) -> PropMap {
+        let mut metadata: PropMap = HashMap::new();
+
+        if let Some(track) = &self.track {
+            let track_id = Path::from(format!("/org/mpressed/FakePlayer/Track/{}", self.track_number));
+            metadata.insert("mpris:trackid".to_string(), Variant(Box::new(track_id)));
+            metadata.insert("xesam:artist".to_string(), Variant(Box::new(vec!(track.artist.clone()))));
+            metadata.insert("xesam:album".to_string(), Variant(Box::new(track.album.clone())));
+            metadata.insert("xesam:title".to_string(), Variant(Box::new(track.title.clone())));
+            if let Some(url) = &track.url {
+                metadata.insert("xesam:url".to_string(), Variant(Box::new(url.clone())));
+            }
+            if let Some(length) = track.length {
+                metadata.insert("mpris:length".to_string(), Variant(Box::new(length.as_micros() as i64)));
+            }
+        }
+
+        metadata
+    }
+
+    fn properties(&self, interface: &str) -> PropMap {
+        let mut properties: PropMap = HashMap::new();
+        let mut insert = |name: &str, value: Box<dyn RefArg>| {
+            properties.insert(name.to_string(), Variant(value));
+        };
+
+        match interface {
+            ROOT_INTERFACE => {
+                insert("Identity", Box::new(self.identity.clone()));
+                insert("CanQuit", Box::new(false));
+                insert("CanRaise", Box::new(false));
+                insert("HasTrackList", Box::new(false));
+                insert("SupportedUriSchemes", Box::<Vec<String>>::default());
+                insert("SupportedMimeTypes", Box::<Vec<String>>::default());
+            }
+            PLAYER_INTERFACE => {
+                insert("PlaybackStatus", Box::new(self.playback_status().to_string()));
+                insert("LoopStatus", Box::new("None".to_string()));
+                insert("Rate", Box::new(1.0));
+                insert("MinimumRate", Box::new(1.0));
+                insert("MaximumRate", Box::new(1.0));
+                insert("Shuffle", Box::new(false));
+                insert("Metadata", Box::new(self.metadata()));
+                insert("Volume", Box::new(1.0));
+                insert("Position", Box::new(self.position().as_micros() as i64));
+                insert("CanGoNext", Box::new(false));
+                insert("CanGoPrevious", Box::new(false));
+                insert("CanPlay", Box::new(true));
+                insert("CanPause", Box::new(true));
+                insert("CanSeek", Box::new(false));
+                insert("CanControl", Box::new(false));
+            }
+            _ => {}
+        }
+
+        properties
+    }
+}
+
+fn main() {
+    let (identity, steps) = parse_args(env::args().skip(1).collect()).unwrap_or_else(|err| {
+        eprintln!("{}\n{}", err, USAGE);
+        exit(1);
+    });
+
+    let state = Rc::new(RefCell::new(FakeState {
+        identity,
+        playing: false,
+        track: None,
+        track_number: 0,
+        position: Duration::ZERO,
+        since: Instant::now(),
+    }));
+
+    let connection = LocalConnection::new_session().expect("Could not connect to D-Bus");
+
+    let handler_state = state.clone();
+    connection.start_receive(MatchRule::new_method_call(), Box::new(move |message, connection| {
+        if let Some(reply) = handle(&handler_state.borrow(), &message) {
+            let _ = connection.send(reply);
+        }
+        true
+    }));
+
+    // unique per process so several fake players can share a bus
+    let bus_name = format!("{}.mpressed_fake_player.instance{}", ROOT_INTERFACE, std::process::id());
+    connection.request_name(bus_name.as_str(), false, true, true)
+        .expect("Failed to request bus name");
+
+    for step in steps {
+        let seconds = {
+            let mut state = state.borrow_mut();
+            match step {
+                Step::Play(track, seconds) => {
+                    let same = state.track.as_ref().is_some_and(|current| current.title == track.title && current.artist == track.artist && current.album == track.album);
+                    if !same {
+                        state.track = Some(track);
+                        state.track_number += 1;
+                        state.restart();
+                    }
+                    state.set_playing(true);
+                    seconds
+                }
+                Step::Replay(seconds) => {
+                    state.restart();
+                    state.set_playing(true);
+                    seconds
+                }
+                Step::Pause(seconds) => {
+                    state.set_playing(false);
+                    seconds
+                }
+            }
+        };
+
+        emit_changed(&connection, &state.borrow());
+        serve_for(&connection, Duration::from_secs(seconds));
+    }
+}
+
+fn parse_args(args: Vec<String>) -> Result<(String, Vec<Step>), String> {
+    let mut identity = "Fake Player".to_string();
+    let mut url = None;
+    let mut length = None;
+    let mut steps = vec!();
+    let mut args = args.into_iter();
+
+    let seconds = |arg: Option<String>| -> Result<u64, String> {
+        let arg = arg.ok_or("Missing SECONDS")?;
+        arg.parse().map_err(|_| format!("Invalid SECONDS: {}", arg))
+    };
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--identity" => identity = args.next().ok_or("Missing NAME")?,
+            "url" => url = Some(args.next().ok_or("Missing URL")?),
+            "length" => length = Some(Duration::from_secs(seconds(args.next())?)),
+            "play" => {
+                let mut field = || args.next().ok_or("Missing ARTIST, ALBUM or TITLE");
+                let track = FakeTrack {
+                    artist: field()?,
+                    album: field()?,
+                    title: field()?,
+                    url: url.clone(),
+                    length,
+                };
+                steps.push(Step::Play(track, seconds(args.next())?));
+            }
+            "replay" => steps.push(Step::Replay(seconds(args.next())?)),
+            "pause" => steps.push(Step::Pause(seconds(args.next())?)),
+            _ => return Err(format!("Unknown step: {}", arg)),
+        }
+    }
+
+    Ok((identity, steps))
+}
+
+fn handle(state: &FakeState, message: &Message) -> Option<Message> {
+    if message.msg_type() != MessageType::MethodCall {
+        return None;
+    }
+
+    let interface = message.interface()?;
+    let member = message.member()?;
+
+    let reply = match (&*interface, &*member) {
+        ("org.freedesktop.DBus.Properties", "Get") => {
+            let (interface, name): (&str, &str) = message.read2().ok()?;
+            match state.properties(interface).remove(name) {
+                Some(value) => message.method_return().append1(value),
+                None => error(message, "org.freedesktop.DBus.Error.UnknownProperty", name),
+            }
+        }
+        ("org.freedesktop.DBus.Properties", "GetAll") => {
+            let interface: &str = message.read1().ok()?;
+            message.method_return().append1(state.properties(interface))
+        }
+        ("org.freedesktop.DBus.Introspectable", "Introspect") => message.method_return().append1(INTROSPECTION),
+        ("org.freedesktop.DBus.Peer", "Ping") => message.method_return(),
+        _ => error(message, "org.freedesktop.DBus.Error.UnknownMethod", &member),
+    };
+
+    Some(reply)
+}
+
+fn error(message: &Message, name: &str, text: &str) -> Message {
+    message.error(&ErrorName::from(name), &CString::new(text).unwrap_or_default())
+}
+
+// the same signals a real player sends when it changes track, pauses or seeks
+fn emit_changed(connection: &LocalConnection, state: &FakeState) {
+    let path = Path::from(OBJECT_PATH);
+
+    let mut changed_properties = state.properties(PLAYER_INTERFACE);
+    changed_properties.retain(|name, _| name == "PlaybackStatus" || name == "Metadata");
+
+    let changed = PropertiesPropertiesChanged {
+        interface_name: PLAYER_INTERFACE.to_string(),
+        changed_properties,
+        invalidated_properties: vec!(),
+    };
+    let _ = connection.send(changed.to_emit_message(&path));
+
+    let seeked = Message::signal(&path, &PLAYER_INTERFACE.into(), &"Seeked".into())
+        .append1(state.position().as_micros() as i64);
+    let _ = connection.send(seeked);
+}
+
+fn serve_for(connection: &LocalConnection, duration: Duration) {
+    let end = Instant::now() + duration;
+    while let Some(left) = end.checked_duration_since(Instant::now()) {
+        connection.process(left).expect("Lost connection to D-Bus");
+    }
+}
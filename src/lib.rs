@@ -1,7 +1,21 @@
+use std::env;
 use std::fs::create_dir_all;
 use std::path::PathBuf;
+use std::time::Duration;
+use chrono::TimeDelta;
 use dirs::home_dir;
 
+pub mod config;
+pub mod db;
+pub mod discovery;
+pub mod exclude;
+pub mod now_playing;
+pub mod pause;
+pub mod period;
+pub mod stats;
+pub mod tracker;
+pub mod wrapped;
+
 // pub const FILE_NAME: &str = "test.db";
 pub const FILE_NAME: &str = "mpressed.db";
 pub const MIN_PLAYTIME_MS: i64 = 60000;
@@ -11,10 +25,54 @@ pub struct SongData {
     pub artist: String,
     pub album: String,
     pub title: String,
+    // empty when the player doesn't report one
+    pub album_artist: String,
 }
 
-pub fn get_db_path() -> PathBuf {
-    let full_path = home_dir().unwrap().join(PathBuf::from(".config/mpressed"));
+// everything known about what a player is playing, not just what gets stored
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Track {
+    pub song: SongData,
+    pub url: Option<String>,
+    pub length: Option<Duration>,
+    pub player: String,
+}
+
+// MPRESSED_CONFIG_DIR moves the database and config, mostly so tests don't touch the real ones
+pub fn get_config_dir() -> PathBuf {
+    let full_path = match env::var_os("MPRESSED_CONFIG_DIR") {
+        Some(dir) => PathBuf::from(dir),
+        None => home_dir().unwrap().join(PathBuf::from(".config/mpressed")),
+    };
     create_dir_all(&full_path).unwrap();
-    full_path.join(FILE_NAME)
+    full_path
+}
+
+pub fn get_db_path() -> PathBuf {
+    get_config_dir().join(FILE_NAME)
+}
+
+// parses durations like 90s, 30m, 2h or 1d, a bare number is minutes
+pub fn parse_duration(input: &str) -> Option<TimeDelta> {
+    let input = input.trim();
+    let split = input.find(|c: char| !c.is_ascii_digit()).unwrap_or(input.len());
+    let (number, unit) = input.split_at(split);
+    let number: i64 = number.parse().ok()?;
+
+    match unit {
+        "s" => TimeDelta::try_seconds(number),
+        "" | "m" => TimeDelta::try_minutes(number),
+        "h" => TimeDelta::try_hours(number),
+        "d" => TimeDelta::try_days(number),
+        _ => None,
+    }
+}
+
+// 1h 05m, or 3m 20s under an hour
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    match seconds >= 3600 {
+        true => format!("{}h {:02}m", seconds / 3600, seconds % 3600 / 60),
+        false => format!("{}m {:02}s", seconds / 60, seconds % 60),
+    }
 }
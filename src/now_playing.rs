@@ -0,0 +1,130 @@
+use std::fs::{read_to_string, remove_file, rename, write};
+use std::io;
+use std::path::PathBuf;
+use chrono::{DateTime, Local, TimeDelta};
+use crate::config::Config;
+use crate::{get_config_dir, SongData};
+
+// rewritten by the daemon every tick while a player is followed so the client can show it
+pub const NOW_PLAYING_FILE_NAME: &str = "now_playing";
+
+// a daemon that was killed can't remove the file, anything older than this is ignored
+pub const STALE_AFTER: TimeDelta = TimeDelta::seconds(5);
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct NowPlaying {
+    pub updated: DateTime<Local>,
+    pub player: String,
+    pub playing: bool,
+    pub song: SongData,
+    pub playtime_ms: i64,
+    pub min_playtime_ms: i64,
+    // played for long enough, whether or not it was recorded
+    pub counted: bool,
+    // tracking is paused, so it won't be recorded
+    pub paused: bool,
+    // the rule that keeps it from being recorded
+    pub excluded: Option<String>,
+}
+
+impl NowPlaying {
+    // key = value lines, read back with the config parser
+    fn to_file(&self) -> String {
+        let mut lines = vec!(
+            ("updated", self.updated.to_rfc3339()),
+            ("player", self.player.clone()),
+            ("playing", self.playing.to_string()),
+            ("artist", self.song.artist.clone()),
+            ("album", self.song.album.clone()),
+            ("title", self.song.title.clone()),
+            ("album_artist", self.song.album_artist.clone()),
+            ("playtime_ms", self.playtime_ms.to_string()),
+            ("min_playtime_ms", self.min_playtime_ms.to_string()),
+            ("counted", self.counted.to_string()),
+            ("paused", self.paused.to_string()),
+        );
+        if let Some(excluded) = &self.excluded {
+            lines.push(("excluded", excluded.clone()));
+        }
+
+        lines.into_iter()
+            .map(|(key, value)| format!("{} = {}\n", key, value.replace('\n', " ")))
+            .collect()
+    }
+
+    fn from_file(contents: &str) -> Option<Self> {
+        let config = Config::parse(contents);
+        let get = |key: &str| config.get("", key).map(str::to_string);
+
+        Some(Self {
+            updated: DateTime::parse_from_rfc3339(&get("updated")?).ok()?.with_timezone(&Local),
+            player: get("player")?,
+            playing: get("playing")?.parse().ok()?,
+            song: SongData {
+                artist: get("artist")?,
+                album: get("album")?,
+                title: get("title")?,
+                album_artist: get("album_artist").unwrap_or_default(),
+            },
+            playtime_ms: get("playtime_ms")?.parse().ok()?,
+            min_playtime_ms: get("min_playtime_ms")?.parse().ok()?,
+            counted: get("counted")?.parse().ok()?,
+            paused: get("paused")?.parse().ok()?,
+            excluded: get("excluded"),
+        })
+    }
+}
+
+pub fn get_now_playing_path() -> PathBuf {
+    get_config_dir().join(NOW_PLAYING_FILE_NAME)
+}
+
+// None when nothing is being followed or the daemon stopped updating the file
+pub fn now_playing() -> Option<NowPlaying> {
+    let now_playing = NowPlaying::from_file(&read_to_string(get_now_playing_path()).ok()?)?;
+    (Local::now() - now_playing.updated < STALE_AFTER).then_some(now_playing)
+}
+
+// written next to the file and renamed over it so the client never reads half of it
+pub fn set_now_playing(now_playing: &NowPlaying) -> io::Result<()> {
+    let path = get_now_playing_path();
+    let temporary = path.with_extension("tmp");
+    write(&temporary, now_playing.to_file())?;
+    rename(temporary, path)
+}
+
+pub fn clear_now_playing() -> io::Result<()> {
+    match remove_file(get_now_playing_path()) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_round_trips() {
+        let now_playing = NowPlaying {
+            updated: DateTime::parse_from_rfc3339("2024-01-01T12:00:00+00:00").unwrap().with_timezone(&Local),
+            player: "VLC media player".to_string(),
+            playing: true,
+            song: SongData {
+                artist: "Artist".to_string(),
+                album: "Album = Live".to_string(),
+                title: "Title\nwith a newline".to_string(),
+                album_artist: String::new(),
+            },
+            playtime_ms: 42000,
+            min_playtime_ms: 60000,
+            counted: false,
+            paused: true,
+            excluded: Some("domain = youtube.com".to_string()),
+        };
+
+        let read = NowPlaying::from_file(&now_playing.to_file()).unwrap();
+        assert_eq!(read.song.title, "Title with a newline");
+        assert_eq!(read, NowPlaying { song: read.song.clone(), ..now_playing });
+    }
+}
@@ -0,0 +1,147 @@
+use std::fs::{read_to_string, remove_file, write};
+use std::io;
+use std::path::{Path, PathBuf};
+use chrono::{DateTime, Local, TimeDelta};
+use crate::get_config_dir;
+
+// while this file exists the daemon keeps following the player but doesn't record plays
+pub const PAUSE_FILE_NAME: &str = "paused";
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum PauseState {
+    Recording,
+    // None means paused until resumed by hand
+    Paused(Option<DateTime<Local>>),
+}
+
+impl PauseState {
+    pub fn is_paused(&self) -> bool {
+        matches!(self, PauseState::Paused(_))
+    }
+}
+
+pub fn get_pause_path() -> PathBuf {
+    get_config_dir().join(PAUSE_FILE_NAME)
+}
+
+// only reads the flag file, the daemon removes it once its expiry has passed
+pub fn pause_state() -> PauseState {
+    read_pause_state(&get_pause_path())
+}
+
+fn read_pause_state(path: &Path) -> PauseState {
+    let Ok(contents) = read_to_string(path) else {
+        return PauseState::Recording;
+    };
+
+    let contents = contents.trim();
+    if contents.is_empty() {
+        return PauseState::Paused(None);
+    }
+
+    match DateTime::parse_from_rfc3339(contents) {
+        Ok(until) if until > Local::now() => PauseState::Paused(Some(until.with_timezone(&Local))),
+        Ok(_) => PauseState::Recording,
+        // a hand written flag file with garbage in it still means paused
+        Err(_) => PauseState::Paused(None),
+    }
+}
+
+// for the daemon, which also removes the flag file once its expiry has passed
+pub fn is_paused() -> bool {
+    expire_pause(&get_pause_path()).is_paused()
+}
+
+fn expire_pause(path: &Path) -> PauseState {
+    let state = read_pause_state(path);
+    if !state.is_paused() {
+        let _ = remove_file(path);
+    }
+    state
+}
+
+pub fn pause(duration: Option<TimeDelta>) -> io::Result<PauseState> {
+    write_pause(&get_pause_path(), duration)
+}
+
+fn write_pause(path: &Path, duration: Option<TimeDelta>) -> io::Result<PauseState> {
+    let until = duration.map(|duration| Local::now() + duration);
+    let contents = until.map(|until| until.to_rfc3339()).unwrap_or_default();
+    write(path, contents)?;
+    Ok(PauseState::Paused(until))
+}
+
+pub fn resume() -> io::Result<PauseState> {
+    match remove_file(get_pause_path()) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+        _ => Ok(PauseState::Recording),
+    }
+}
+
+pub fn toggle(duration: Option<TimeDelta>) -> io::Result<PauseState> {
+    if is_paused() {
+        resume()
+    } else {
+        pause(duration)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env::temp_dir;
+    use std::process;
+    use crate::parse_duration;
+    use super::*;
+
+    fn pause_path(name: &str) -> PathBuf {
+        let path = temp_dir().join(format!("mpressed-pause-{}-{}", name, process::id()));
+        let _ = remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("90s"), TimeDelta::try_seconds(90));
+        assert_eq!(parse_duration("30m"), TimeDelta::try_minutes(30));
+        assert_eq!(parse_duration(" 2h "), TimeDelta::try_hours(2));
+        assert_eq!(parse_duration("1d"), TimeDelta::try_days(1));
+        // the pause command takes minutes by default
+        assert_eq!(parse_duration("15"), TimeDelta::try_minutes(15));
+
+        for garbage in ["", "m", "-5m", "1.5h", "10 m", "3w", "forever"] {
+            assert_eq!(parse_duration(garbage), None, "{}", garbage);
+        }
+    }
+
+    #[test]
+    fn pauses_until_resumed() {
+        let path = pause_path("until-resumed");
+        assert_eq!(read_pause_state(&path), PauseState::Recording);
+
+        assert_eq!(write_pause(&path, None).unwrap(), PauseState::Paused(None));
+        assert_eq!(read_pause_state(&path), PauseState::Paused(None));
+
+        // a hand written file that can't be read as a date
+        write(&path, "tomorrow").unwrap();
+        assert_eq!(read_pause_state(&path), PauseState::Paused(None));
+
+        remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn pause_expires() {
+        let path = pause_path("expires");
+
+        let PauseState::Paused(Some(until)) = write_pause(&path, TimeDelta::try_hours(1)).unwrap() else {
+            panic!("pause has no expiry");
+        };
+        assert_eq!(read_pause_state(&path), PauseState::Paused(Some(until)));
+
+        write_pause(&path, TimeDelta::try_seconds(-1)).unwrap();
+        assert_eq!(read_pause_state(&path), PauseState::Recording);
+        assert!(path.exists());
+
+        assert_eq!(expire_pause(&path), PauseState::Recording);
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,160 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+use chrono::{Datelike, Months, NaiveDate, TimeDelta};
+use crate::stats::DateStat;
+
+// how dates are grouped together, weeks are ISO weeks starting on Monday
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Period {
+    #[default]
+    Day,
+    Week,
+    Month,
+    Year,
+}
+
+impl Period {
+    pub fn name(self) -> &'static str {
+        match self {
+            Period::Day => "Day",
+            Period::Week => "Week",
+            Period::Month => "Month",
+            Period::Year => "Year",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            Period::Day => Period::Week,
+            Period::Week => Period::Month,
+            Period::Month => Period::Year,
+            Period::Year => Period::Day,
+        }
+    }
+
+    // first day of the period date is in
+    pub fn start(self, date: NaiveDate) -> NaiveDate {
+        match self {
+            Period::Day => date,
+            Period::Week => date - TimeDelta::days(date.weekday().num_days_from_monday() as i64),
+            Period::Month => date.with_day(1).unwrap_or(date),
+            Period::Year => date.with_ordinal(1).unwrap_or(date),
+        }
+    }
+
+    // first day of the following period
+    pub fn after(self, start: NaiveDate) -> NaiveDate {
+        match self {
+            Period::Day => start + TimeDelta::days(1),
+            Period::Week => start + TimeDelta::days(7),
+            Period::Month => start + Months::new(1),
+            Period::Year => start + Months::new(12),
+        }
+    }
+
+    // 2024-03-15, 2024-W11, 2024-03 or 2024
+    pub fn label(self, start: NaiveDate) -> String {
+        match self {
+            Period::Day => start.to_string(),
+            Period::Week => format!("{}-W{:02}", start.iso_week().year(), start.iso_week().week()),
+            Period::Month => start.format("%Y-%m").to_string(),
+            Period::Year => start.year().to_string(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PeriodStat {
+    pub start: NaiveDate,
+    pub label: String,
+    pub plays: u32,
+    pub listening_time: Duration,
+}
+
+// adds up dates per period in date order, with a zero row for every period without plays
+// between from and to, or between the first and last date when they are None
+pub fn period_totals(dates: &[DateStat], period: Period, from: Option<NaiveDate>, to: Option<NaiveDate>) -> Vec<PeriodStat> {
+    let mut totals: BTreeMap<NaiveDate, (u32, Duration)> = BTreeMap::new();
+    for date in dates {
+        let Ok(day) = date.date.parse::<NaiveDate>() else {
+            continue;
+        };
+        let total = totals.entry(period.start(day)).or_default();
+        total.0 += date.plays;
+        total.1 += date.listening_time;
+    }
+
+    let (Some(first), Some(last)) = (
+        from.map(|from| period.start(from)).or(totals.keys().next().copied()),
+        to.map(|to| period.start(to)).or(totals.keys().next_back().copied()),
+    ) else {
+        return vec!();
+    };
+
+    let mut stats = vec!();
+    let mut start = first;
+    while start <= last {
+        let (plays, listening_time) = totals.get(&start).copied().unwrap_or_default();
+        stats.push(PeriodStat {
+            start,
+            label: period.label(start),
+            plays,
+            listening_time,
+        });
+        start = period.after(start);
+    }
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(text: &str) -> NaiveDate {
+        text.parse().unwrap()
+    }
+
+    fn day(text: &str, plays: u32) -> DateStat {
+        DateStat {
+            date: text.to_string(),
+            plays,
+            listening_time: Duration::from_secs(plays as u64 * 60),
+        }
+    }
+
+    fn labels(stats: &[PeriodStat]) -> Vec<(&str, u32)> {
+        stats.iter().map(|stat| (stat.label.as_str(), stat.plays)).collect()
+    }
+
+    #[test]
+    fn iso_weeks_start_on_monday() {
+        // a Sunday at the end of week 52 of 2023
+        assert_eq!(Period::Week.start(date("2023-12-31")), date("2023-12-25"));
+        assert_eq!(Period::Week.label(date("2023-12-25")), "2023-W52");
+        // the Monday of week 1 of 2025 is still in 2024
+        assert_eq!(Period::Week.label(Period::Week.start(date("2025-01-01"))), "2025-W01");
+    }
+
+    #[test]
+    fn days_are_zero_filled() {
+        let totals = period_totals(&[day("2024-01-03", 2), day("2024-01-01", 1)], Period::Day, None, None);
+        assert_eq!(labels(&totals), [("2024-01-01", 1), ("2024-01-02", 0), ("2024-01-03", 2)]);
+    }
+
+    #[test]
+    fn months_add_up() {
+        let dates = [day("2024-01-31", 1), day("2024-01-01", 2), day("2024-03-15", 4)];
+        let totals = period_totals(&dates, Period::Month, None, None);
+
+        assert_eq!(labels(&totals), [("2024-01", 3), ("2024-02", 0), ("2024-03", 4)]);
+        assert_eq!(totals[0].listening_time, Duration::from_secs(180));
+    }
+
+    #[test]
+    fn range_bounds_extend_the_periods() {
+        let totals = period_totals(&[day("2024-06-01", 1)], Period::Year, Some(date("2023-05-01")), Some(date("2025-01-01")));
+        assert_eq!(labels(&totals), [("2023", 0), ("2024", 1), ("2025", 0)]);
+
+        assert!(period_totals(&[], Period::Week, None, None).is_empty());
+    }
+}
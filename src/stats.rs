@@ -0,0 +1,780 @@
+use std::path::Path;
+use std::time::Duration;
+use chrono::NaiveDate;
+use rusqlite::{Connection, Params, Row};
+use crate::{db, SongData};
+
+// every query takes the same filter parameters so they share one WHERE clause,
+// NULL means unfiltered and a LIMIT of -1 means no limit
+fn filter_clause() -> String {
+    format!("{}
+    AND (?6 IS NULL OR song_plays.id = ?6)", other_songs_clause())
+}
+
+// the filter without the song, for comparing the filtered song with every other one
+fn other_songs_clause() -> String {
+    let album_artist = db::album_artist("filtered");
+
+    format!("(?1 IS NULL OR song_plays.date >= ?1)
+    AND (?2 IS NULL OR song_plays.date <= ?2)
+    AND (?3 IS NULL OR song_plays.player = ?3)
+    AND (?4 IS NULL OR song_plays.device = ?4)
+    AND (?7 IS NULL OR song_plays.id IN (SELECT id FROM song_data AS filtered WHERE filtered.artist = ?7))
+    AND (?9 IS NULL OR song_plays.id IN (SELECT id FROM song_data AS filtered WHERE filtered.album = ?9 AND {album_artist} = ?8))")
+}
+
+// a play this many days old counts half, one twice as old a third.
+// the weight is scale / (scale + age), not a half-life, as sqlite can be built without pow()
+pub const RECENCY_SCALE_DAYS: u32 = 30;
+
+// plays weighted by how recent they are, see RECENCY_SCALE_DAYS.
+// age is counted from today, or from the end of the range when it ends earlier
+fn recency_column() -> String {
+    let scale = RECENCY_SCALE_DAYS as f64;
+
+    format!("SUM(song_plays.plays * {scale:.1} / ({scale:.1}
+        + julianday(MIN(COALESCE(?2, date('now', 'localtime')), date('now', 'localtime'))) - julianday(song_plays.date)))")
+}
+
+// only songs whose length is known add to it
+const LISTENING_TIME_COLUMN: &str = "SUM(song_plays.plays * COALESCE(song_data.length, 0))";
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Filter {
+    pub from: Option<NaiveDate>,
+    pub to: Option<NaiveDate>,
+    pub player: Option<String>,
+    pub device: Option<String>,
+    pub limit: Option<usize>,
+    pub song: Option<i64>,
+    pub artist: Option<String>,
+    // album artist and album, the same key top_albums groups on
+    pub album: Option<(String, String)>,
+}
+
+impl Filter {
+    // both ends inclusive
+    pub fn range(mut self, from: Option<NaiveDate>, to: Option<NaiveDate>) -> Self {
+        self.from = from;
+        self.to = to;
+        self
+    }
+
+    pub fn player(mut self, player: Option<String>) -> Self {
+        self.player = player;
+        self
+    }
+
+    pub fn device(mut self, device: Option<String>) -> Self {
+        self.device = device;
+        self
+    }
+
+    pub fn limit(mut self, limit: Option<usize>) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    pub fn song(mut self, song: Option<i64>) -> Self {
+        self.song = song;
+        self
+    }
+
+    pub fn artist(mut self, artist: Option<String>) -> Self {
+        self.artist = artist;
+        self
+    }
+
+    pub fn album(mut self, album: Option<(String, String)>) -> Self {
+        self.album = album;
+        self
+    }
+
+    fn params(&self) -> impl Params + '_ {
+        (
+            self.from.map(|date| date.to_string()),
+            self.to.map(|date| date.to_string()),
+            &self.player,
+            &self.device,
+            self.limit.map_or(-1, |limit| limit as i64),
+            self.song,
+            &self.artist,
+            self.album.as_ref().map(|(artist, _)| artist),
+            self.album.as_ref().map(|(_, album)| album),
+        )
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SongStat {
+    pub id: i64,
+    pub song: SongData,
+    pub plays: u32,
+    pub listening_time: Duration,
+    pub recency: f64,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ArtistStat {
+    pub artist: String,
+    pub plays: u32,
+    // every song known for the artist, played in the filtered range or not
+    pub songs: u32,
+    // the songs with plays matching the filter
+    pub songs_played: u32,
+    pub listening_time: Duration,
+    pub recency: f64,
+}
+
+// albums with the same title by different artists are different albums
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AlbumStat {
+    pub artist: String,
+    pub album: String,
+    pub plays: u32,
+    pub songs: u32,
+    pub songs_played: u32,
+    pub listening_time: Duration,
+    pub recency: f64,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DateStat {
+    pub date: String,
+    pub plays: u32,
+    // only songs whose length is known add to it
+    pub listening_time: Duration,
+}
+
+// dates are YYYY-MM-DD, None when the song has no plays matching the filter
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SongSummary {
+    pub first_played: String,
+    pub last_played: String,
+    pub plays: u32,
+    // None if the player never reported the song's length
+    pub listening_time: Option<Duration>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SongMonth {
+    // YYYY-MM
+    pub month: String,
+    pub plays: u32,
+    // 1 for the most played song of the month, ties share a rank
+    pub rank: u32,
+}
+
+// first_played is the first play ever, not just the first one matching the filter
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ArtistFirstPlay {
+    pub artist: String,
+    pub first_played: String,
+}
+
+// likewise the first play ever
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SongFirstPlay {
+    pub id: i64,
+    pub song: SongData,
+    pub first_played: String,
+}
+
+// keyed on album artist and album like top_albums, first_played is the first play ever
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AlbumFirstPlay {
+    pub artist: String,
+    pub album: String,
+    pub first_played: String,
+}
+
+// plays of one song on one date
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SongDay {
+    pub id: i64,
+    pub date: String,
+    pub plays: u32,
+}
+
+// plays per player or per device
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SourceStat {
+    pub source: String,
+    pub plays: u32,
+}
+
+#[derive(Debug)]
+pub struct Stats {
+    db: Connection,
+}
+
+impl Stats {
+    pub fn open<P: AsRef<Path>>(path: P) -> rusqlite::Result<Self> {
+        Ok(Self { db: db::open(path)? })
+    }
+
+    pub fn open_in_memory() -> rusqlite::Result<Self> {
+        Self::from_connection(Connection::open_in_memory()?)
+    }
+
+    pub fn from_connection(db: Connection) -> rusqlite::Result<Self> {
+        db::init(&db)?;
+        Ok(Self { db })
+    }
+
+    pub fn connection(&self) -> &Connection {
+        &self.db
+    }
+
+    // changes whenever another connection, like the daemon's, commits to the database
+    pub fn data_version(&self) -> rusqlite::Result<i64> {
+        self.db.query_row("PRAGMA data_version", [], |row| row.get(0))
+    }
+
+    // adds one play, returns false if the song already had plays for this date, player and device
+    pub fn record_play(&self, song: &SongData, date: &str, player: &str, device: &str, length: Option<Duration>) -> rusqlite::Result<bool> {
+        self.db.execute("INSERT OR IGNORE INTO song_data (artist, album, title, album_artist) VALUES (?1, ?2, ?3, ?4)",
+                        (&song.artist, &song.album, &song.title, &song.album_artist))?;
+
+        let id: i64 = self.db.query_row("SELECT id FROM song_data WHERE artist = ?1 AND album = ?2 AND title = ?3",
+                                        (&song.artist, &song.album, &song.title), |row| row.get(0))?;
+
+        // songs first seen without an album artist keep the first one reported
+        if !song.album_artist.is_empty() {
+            self.db.execute("UPDATE song_data SET album_artist = ?2 WHERE id = ?1 AND album_artist = ''",
+                            (id, &song.album_artist))?;
+        }
+
+        // likewise the first length reported is kept
+        if let Some(length) = length {
+            self.db.execute("UPDATE song_data SET length = ?2 WHERE id = ?1 AND length IS NULL",
+                            (id, length.as_millis() as i64))?;
+        }
+
+        let updated = self.db.execute("UPDATE song_plays SET plays = plays + 1 WHERE id = ?1 AND date = ?2 AND player = ?3 AND device = ?4",
+                                      (id, date, player, device))?;
+
+        if updated == 0 {
+            self.db.execute("INSERT INTO song_plays (id, date, plays, player, device) VALUES (?1, ?2, 1, ?3, ?4)",
+                            (id, date, player, device))?;
+        }
+
+        Ok(updated == 0)
+    }
+
+    pub fn top_songs(&self, filter: &Filter) -> rusqlite::Result<Vec<SongStat>> {
+        let filter_clause = filter_clause();
+        let recency = recency_column();
+
+        self.query(&format!("SELECT song_data.id, artist, album, title, SUM(plays), album_artist, {LISTENING_TIME_COLUMN}, {recency} FROM song_data
+                JOIN song_plays ON song_data.id = song_plays.id
+                WHERE {filter_clause}
+                GROUP BY song_data.id ORDER BY SUM(plays) DESC, song_data.id LIMIT ?5"),
+            filter,
+            |row| Ok(SongStat {
+                id: row.get(0)?,
+                song: SongData {
+                    artist: row.get(1)?,
+                    album: row.get(2)?,
+                    title: row.get(3)?,
+                    album_artist: row.get(5)?,
+                },
+                plays: row.get(4)?,
+                listening_time: Duration::from_millis(row.get(6)?),
+                recency: row.get(7)?,
+            }))
+    }
+
+    pub fn top_artists(&self, filter: &Filter) -> rusqlite::Result<Vec<ArtistStat>> {
+        let filter_clause = filter_clause();
+        let recency = recency_column();
+
+        self.query(&format!("SELECT artist, SUM(plays), (SELECT COUNT(*) FROM song_data AS songs WHERE songs.artist = song_data.artist),
+                    COUNT(DISTINCT song_data.id), {LISTENING_TIME_COLUMN}, {recency}
+                FROM song_data
+                JOIN song_plays ON song_data.id = song_plays.id
+                WHERE {filter_clause}
+                GROUP BY artist ORDER BY SUM(plays) DESC, artist LIMIT ?5"),
+            filter,
+            |row| Ok(ArtistStat {
+                artist: row.get(0)?,
+                plays: row.get(1)?,
+                songs: row.get(2)?,
+                songs_played: row.get(3)?,
+                listening_time: Duration::from_millis(row.get(4)?),
+                recency: row.get(5)?,
+            }))
+    }
+
+    pub fn top_albums(&self, filter: &Filter) -> rusqlite::Result<Vec<AlbumStat>> {
+        let album_artist = db::album_artist("song_data");
+        let songs_album_artist = db::album_artist("songs");
+        let filter_clause = filter_clause();
+        let recency = recency_column();
+
+        self.query(&format!("SELECT {album_artist} AS album_key, album, SUM(plays),
+                    (SELECT COUNT(*) FROM song_data AS songs WHERE songs.album = song_data.album AND {songs_album_artist} = {album_artist}),
+                    COUNT(DISTINCT song_data.id), {LISTENING_TIME_COLUMN}, {recency}
+                FROM song_data
+                JOIN song_plays ON song_data.id = song_plays.id
+                WHERE {filter_clause}
+                GROUP BY album_key, album ORDER BY SUM(plays) DESC, album, album_key LIMIT ?5"),
+            filter,
+            |row| Ok(AlbumStat {
+                artist: row.get(0)?,
+                album: row.get(1)?,
+                plays: row.get(2)?,
+                songs: row.get(3)?,
+                songs_played: row.get(4)?,
+                listening_time: Duration::from_millis(row.get(5)?),
+                recency: row.get(6)?,
+            }))
+    }
+
+    // only dates that have plays, most played first
+    pub fn date_totals(&self, filter: &Filter) -> rusqlite::Result<Vec<DateStat>> {
+        let filter_clause = filter_clause();
+
+        self.query(&format!("SELECT date, SUM(plays), SUM(plays * COALESCE(song_data.length, 0)) FROM song_plays
+                JOIN song_data ON song_data.id = song_plays.id
+                WHERE {filter_clause}
+                GROUP BY date ORDER BY SUM(plays) DESC, date LIMIT ?5"),
+            filter,
+            |row| Ok(DateStat {
+                date: row.get(0)?,
+                plays: row.get(1)?,
+                listening_time: Duration::from_millis(row.get(2)?),
+            }))
+    }
+
+    // filter.song is the song to summarise
+    pub fn song_summary(&self, filter: &Filter) -> rusqlite::Result<Option<SongSummary>> {
+        let filter_clause = filter_clause();
+
+        let summary = self.query(&format!("SELECT MIN(date), MAX(date), SUM(plays), MAX(song_data.length) FROM song_plays
+                JOIN song_data ON song_data.id = song_plays.id
+                WHERE {filter_clause}"),
+            filter,
+            |row| {
+                let Some(first_played) = row.get(0)? else {
+                    return Ok(None);
+                };
+                let plays: u32 = row.get(2)?;
+                let length: Option<u64> = row.get(3)?;
+
+                Ok(Some(SongSummary {
+                    first_played,
+                    last_played: row.get(1)?,
+                    plays,
+                    listening_time: length.map(|length| Duration::from_millis(length * plays as u64)),
+                }))
+            })?;
+
+        Ok(summary.into_iter().flatten().next())
+    }
+
+    // plays per month of filter.song and where it ranked among every song played that month,
+    // months without plays of the song are left out
+    pub fn song_months(&self, filter: &Filter) -> rusqlite::Result<Vec<SongMonth>> {
+        let other_songs_clause = other_songs_clause();
+
+        self.query(&format!("WITH monthly AS (
+                    SELECT song_plays.id, substr(date, 1, 7) AS month, SUM(plays) AS plays FROM song_plays
+                    WHERE {other_songs_clause}
+                    GROUP BY song_plays.id, month
+                )
+                SELECT month, plays, (SELECT COUNT(*) FROM monthly AS other WHERE other.month = monthly.month AND other.plays > monthly.plays) + 1
+                FROM monthly
+                WHERE id = ?6
+                ORDER BY month LIMIT ?5"),
+            filter,
+            |row| Ok(SongMonth {
+                month: row.get(0)?,
+                plays: row.get(1)?,
+                rank: row.get(2)?,
+            }))
+    }
+
+    // every artist with plays matching the filter, first discovered first
+    pub fn artist_first_plays(&self, filter: &Filter) -> rusqlite::Result<Vec<ArtistFirstPlay>> {
+        let filter_clause = filter_clause();
+
+        self.query(&format!("SELECT artist, MIN(date) FROM song_data
+                JOIN song_plays ON song_data.id = song_plays.id
+                WHERE artist IN (
+                    SELECT artist FROM song_data
+                    JOIN song_plays ON song_data.id = song_plays.id
+                    WHERE {filter_clause}
+                )
+                GROUP BY artist ORDER BY MIN(date), artist LIMIT ?5"),
+            filter,
+            |row| Ok(ArtistFirstPlay {
+                artist: row.get(0)?,
+                first_played: row.get(1)?,
+            }))
+    }
+
+    // every song with plays matching the filter, first discovered first
+    pub fn song_first_plays(&self, filter: &Filter) -> rusqlite::Result<Vec<SongFirstPlay>> {
+        let filter_clause = filter_clause();
+
+        self.query(&format!("SELECT song_data.id, artist, album, title, album_artist, MIN(date) FROM song_data
+                JOIN song_plays ON song_data.id = song_plays.id
+                WHERE song_data.id IN (
+                    SELECT song_plays.id FROM song_plays
+                    WHERE {filter_clause}
+                )
+                GROUP BY song_data.id ORDER BY MIN(date), song_data.id LIMIT ?5"),
+            filter,
+            |row| Ok(SongFirstPlay {
+                id: row.get(0)?,
+                song: SongData {
+                    artist: row.get(1)?,
+                    album: row.get(2)?,
+                    title: row.get(3)?,
+                    album_artist: row.get(4)?,
+                },
+                first_played: row.get(5)?,
+            }))
+    }
+
+    // every album with plays matching the filter, first discovered first
+    pub fn album_first_plays(&self, filter: &Filter) -> rusqlite::Result<Vec<AlbumFirstPlay>> {
+        let album_artist = db::album_artist("song_data");
+        let played_album_artist = db::album_artist("played");
+        let filter_clause = filter_clause();
+
+        self.query(&format!("SELECT {album_artist} AS album_key, album, MIN(date) FROM song_data
+                JOIN song_plays ON song_data.id = song_plays.id
+                WHERE ({album_artist}, album) IN (
+                    SELECT {played_album_artist}, played.album FROM song_data AS played
+                    JOIN song_plays ON played.id = song_plays.id
+                    WHERE {filter_clause}
+                )
+                GROUP BY album_key, album ORDER BY MIN(date), album, album_key LIMIT ?5"),
+            filter,
+            |row| Ok(AlbumFirstPlay {
+                artist: row.get(0)?,
+                album: row.get(1)?,
+                first_played: row.get(2)?,
+            }))
+    }
+
+    // in date order, then song
+    pub fn song_days(&self, filter: &Filter) -> rusqlite::Result<Vec<SongDay>> {
+        let filter_clause = filter_clause();
+
+        self.query(&format!("SELECT song_plays.id, date, SUM(plays) FROM song_plays
+                WHERE {filter_clause}
+                GROUP BY song_plays.id, date ORDER BY date, song_plays.id LIMIT ?5"),
+            filter,
+            |row| Ok(SongDay {
+                id: row.get(0)?,
+                date: row.get(1)?,
+                plays: row.get(2)?,
+            }))
+    }
+
+    pub fn players(&self, filter: &Filter) -> rusqlite::Result<Vec<SourceStat>> {
+        self.sources("player", filter)
+    }
+
+    pub fn devices(&self, filter: &Filter) -> rusqlite::Result<Vec<SourceStat>> {
+        self.sources("device", filter)
+    }
+
+    fn sources(&self, column: &str, filter: &Filter) -> rusqlite::Result<Vec<SourceStat>> {
+        let filter_clause = filter_clause();
+
+        self.query(&format!("SELECT {column}, SUM(plays) FROM song_plays
+                WHERE {filter_clause}
+                GROUP BY {column} ORDER BY SUM(plays) DESC, {column} LIMIT ?5"),
+            filter,
+            |row| Ok(SourceStat {
+                source: row.get(0)?,
+                plays: row.get(1)?,
+            }))
+    }
+
+    fn query<T, F: FnMut(&Row<'_>) -> rusqlite::Result<T>>(&self, sql: &str, filter: &Filter, f: F) -> rusqlite::Result<Vec<T>> {
+        self.db.prepare_cached(sql)?
+            .query_map(filter.params(), f)?
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn song(artist: &str, album: &str, title: &str) -> SongData {
+        SongData {
+            artist: artist.to_string(),
+            album: album.to_string(),
+            title: title.to_string(),
+            ..SongData::default()
+        }
+    }
+
+    fn date(date: &str) -> Option<NaiveDate> {
+        date.parse().ok()
+    }
+
+    // a: 3 plays over two days, b: 2 plays, c: 1 play on another device
+    fn stats() -> Stats {
+        let stats = Stats::open_in_memory().unwrap();
+        let a = song("Artist", "Album", "a");
+        let b = song("Artist", "Other", "b");
+        let c = song("Someone", "Album", "c");
+
+        stats.record_play(&a, "2024-01-01", "VLC", "desktop", None).unwrap();
+        stats.record_play(&a, "2024-01-01", "VLC", "desktop", None).unwrap();
+        stats.record_play(&a, "2024-01-02", "VLC", "desktop", None).unwrap();
+        stats.record_play(&b, "2024-01-02", "Brave", "desktop", None).unwrap();
+        stats.record_play(&b, "2024-01-03", "VLC", "desktop", None).unwrap();
+        stats.record_play(&c, "2024-01-03", "VLC", "laptop", None).unwrap();
+        stats
+    }
+
+    #[test]
+    fn record_play_counts_per_source() {
+        let stats = Stats::open_in_memory().unwrap();
+        let a = song("Artist", "Album", "a");
+
+        assert!(stats.record_play(&a, "2024-01-01", "VLC", "desktop", None).unwrap());
+        assert!(!stats.record_play(&a, "2024-01-01", "VLC", "desktop", None).unwrap());
+        assert!(stats.record_play(&a, "2024-01-01", "VLC", "laptop", None).unwrap());
+
+        let rows: u32 = stats.connection().query_row("SELECT COUNT(*) FROM song_plays", [], |row| row.get(0)).unwrap();
+        assert_eq!(rows, 2);
+    }
+
+    #[test]
+    fn top_songs_most_played_first() {
+        let songs = stats().top_songs(&Filter::default()).unwrap();
+
+        let titles: Vec<(&str, u32)> = songs.iter().map(|s| (s.song.title.as_str(), s.plays)).collect();
+        assert_eq!(titles, [("a", 3), ("b", 2), ("c", 1)]);
+    }
+
+    #[test]
+    fn filter_by_date_range() {
+        let filter = Filter::default().range(date("2024-01-02"), date("2024-01-02"));
+        let songs = stats().top_songs(&filter).unwrap();
+
+        let titles: Vec<(&str, u32)> = songs.iter().map(|s| (s.song.title.as_str(), s.plays)).collect();
+        assert_eq!(titles, [("a", 1), ("b", 1)]);
+    }
+
+    #[test]
+    fn filter_by_player_and_device() {
+        let stats = stats();
+
+        let brave = stats.top_songs(&Filter::default().player(Some("Brave".to_string()))).unwrap();
+        assert_eq!(brave.len(), 1);
+        assert_eq!(brave[0].song.title, "b");
+
+        let laptop = stats.top_songs(&Filter::default().device(Some("laptop".to_string()))).unwrap();
+        assert_eq!(laptop.len(), 1);
+        assert_eq!(laptop[0].song.title, "c");
+    }
+
+    #[test]
+    fn filter_by_artist_album_and_song() {
+        let stats = stats();
+
+        let albums = stats.top_albums(&Filter::default().artist(Some("Artist".to_string()))).unwrap();
+        assert_eq!(albums.iter().map(|album| album.album.as_str()).collect::<Vec<_>>(), ["Album", "Other"]);
+        assert_eq!(albums[0].plays, 3);
+
+        let songs = stats.top_songs(&Filter::default().album(Some(("Someone".to_string(), "Album".to_string())))).unwrap();
+        assert_eq!(songs.len(), 1);
+        assert_eq!(songs[0].song.title, "c");
+
+        let a = stats.top_songs(&Filter::default()).unwrap()[0].id;
+        let dates = stats.date_totals(&Filter::default().song(Some(a))).unwrap();
+        assert_eq!(dates, [
+            DateStat { date: "2024-01-01".to_string(), plays: 2, listening_time: Duration::ZERO },
+            DateStat { date: "2024-01-02".to_string(), plays: 1, listening_time: Duration::ZERO },
+        ]);
+    }
+
+    #[test]
+    fn limit() {
+        let songs = stats().top_songs(&Filter::default().limit(Some(2))).unwrap();
+        assert_eq!(songs.len(), 2);
+    }
+
+    #[test]
+    fn top_artists_and_albums() {
+        let stats = stats();
+
+        let artists = stats.top_artists(&Filter::default()).unwrap();
+        let artists: Vec<(&str, u32, u32, u32)> = artists.iter().map(|a| (a.artist.as_str(), a.plays, a.songs, a.songs_played)).collect();
+        assert_eq!(artists, [("Artist", 5, 2, 2), ("Someone", 1, 1, 1)]);
+
+        // songs counts every song known, songs_played only the ones in the range
+        let artists = stats.top_artists(&Filter::default().range(date("2024-01-03"), None)).unwrap();
+        assert_eq!((artists[0].songs, artists[0].songs_played), (2, 1));
+
+        let albums = stats.top_albums(&Filter::default()).unwrap();
+        let albums: Vec<(&str, &str, u32, u32)> = albums.iter().map(|a| (a.artist.as_str(), a.album.as_str(), a.plays, a.songs)).collect();
+        assert_eq!(albums, [("Artist", "Album", 3, 1), ("Artist", "Other", 2, 1), ("Someone", "Album", 1, 1)]);
+    }
+
+    #[test]
+    fn albums_keyed_on_album_artist() {
+        let stats = Stats::open_in_memory().unwrap();
+        let compilation = |artist: &str, title: &str| SongData {
+            album_artist: "Various Artists".to_string(),
+            ..song(artist, "Hits", title)
+        };
+
+        stats.record_play(&compilation("One", "a"), "2024-01-01", "", "", None).unwrap();
+        stats.record_play(&compilation("Two", "b"), "2024-01-01", "", "", None).unwrap();
+        // no album artist, so the first of the artists
+        stats.record_play(&song("Three / Four", "Hits", "c"), "2024-01-01", "", "", None).unwrap();
+        stats.record_play(&song("Three", "Hits", "d"), "2024-01-01", "", "", None).unwrap();
+
+        let albums = stats.top_albums(&Filter::default()).unwrap();
+        let albums: Vec<(&str, &str, u32, u32, u32)> = albums.iter().map(|a| (a.artist.as_str(), a.album.as_str(), a.plays, a.songs, a.songs_played)).collect();
+        assert_eq!(albums, [("Three", "Hits", 2, 2, 2), ("Various Artists", "Hits", 2, 2, 2)]);
+    }
+
+    #[test]
+    fn listening_time_and_recency() {
+        let stats = stats();
+        stats.record_play(&song("Someone", "Album", "c"), "2024-01-03", "VLC", "desktop", Some(Duration::from_secs(100))).unwrap();
+
+        let songs = stats.top_songs(&Filter::default()).unwrap();
+        let times: Vec<(&str, u64)> = songs.iter().map(|s| (s.song.title.as_str(), s.listening_time.as_secs())).collect();
+        assert_eq!(times, [("a", 0), ("b", 0), ("c", 200)]);
+
+        // counted from today, years after the plays
+        assert!(songs[2].recency < 0.1);
+
+        // b and c have two plays each, c's are both on the last day of the range
+        let songs = stats.top_songs(&Filter::default().range(None, date("2024-01-03"))).unwrap();
+        assert_eq!(songs[2].recency, 2.0);
+        assert_eq!(songs[1].recency, 30.0 / 31.0 + 1.0);
+        assert!(songs[0].recency < 3.0);
+
+        let january_second = stats.top_artists(&Filter::default().range(None, date("2024-01-02"))).unwrap();
+        assert_eq!(january_second[0].recency, 2.0 * 30.0 / 31.0 + 2.0);
+    }
+
+    #[test]
+    fn date_totals() {
+        let dates = stats().date_totals(&Filter::default()).unwrap();
+
+        let totals: Vec<(&str, u32)> = dates.iter().map(|d| (d.date.as_str(), d.plays)).collect();
+        assert_eq!(totals, [("2024-01-01", 2), ("2024-01-02", 2), ("2024-01-03", 2)]);
+    }
+
+    #[test]
+    fn date_listening_time() {
+        let stats = stats();
+        stats.record_play(&song("Artist", "Album", "a"), "2024-01-04", "VLC", "desktop", Some(Duration::from_secs(200))).unwrap();
+
+        let dates = stats.date_totals(&Filter::default().range(date("2024-01-01"), date("2024-01-04"))).unwrap();
+        let times: Vec<(&str, u64)> = dates.iter().map(|d| (d.date.as_str(), d.listening_time.as_secs())).collect();
+        assert_eq!(times, [("2024-01-01", 400), ("2024-01-02", 200), ("2024-01-03", 0), ("2024-01-04", 200)]);
+    }
+
+    #[test]
+    fn song_summary_and_months() {
+        let stats = stats();
+        let a = song("Artist", "Album", "a");
+        stats.record_play(&a, "2024-02-10", "VLC", "desktop", Some(Duration::from_secs(200))).unwrap();
+        stats.record_play(&song("Artist", "Other", "b"), "2024-02-11", "VLC", "desktop", None).unwrap();
+        stats.record_play(&song("Artist", "Other", "b"), "2024-02-12", "VLC", "desktop", None).unwrap();
+
+        let id = stats.top_songs(&Filter::default()).unwrap()[0].id;
+        let filter = Filter::default().song(Some(id));
+
+        assert_eq!(stats.song_summary(&filter).unwrap(), Some(SongSummary {
+            first_played: "2024-01-01".to_string(),
+            last_played: "2024-02-10".to_string(),
+            plays: 4,
+            listening_time: Some(Duration::from_secs(800)),
+        }));
+        assert_eq!(stats.song_summary(&filter.clone().range(date("2025-01-01"), None)).unwrap(), None);
+
+        assert_eq!(stats.song_months(&filter).unwrap(), [
+            SongMonth { month: "2024-01".to_string(), plays: 3, rank: 1 },
+            SongMonth { month: "2024-02".to_string(), plays: 1, rank: 2 },
+        ]);
+    }
+
+    #[test]
+    fn artists_first_played_before_the_range() {
+        let stats = stats();
+        stats.record_play(&song("Newcomer", "Album", "d"), "2024-02-01", "VLC", "desktop", None).unwrap();
+        stats.record_play(&song("Artist", "Album", "a"), "2024-02-02", "VLC", "desktop", None).unwrap();
+
+        let firsts = stats.artist_first_plays(&Filter::default().range(date("2024-02-01"), None)).unwrap();
+        assert_eq!(firsts, [
+            ArtistFirstPlay { artist: "Artist".to_string(), first_played: "2024-01-01".to_string() },
+            ArtistFirstPlay { artist: "Newcomer".to_string(), first_played: "2024-02-01".to_string() },
+        ]);
+    }
+
+    #[test]
+    fn songs_and_albums_first_played() {
+        let stats = stats();
+        stats.record_play(&song("Artist", "Album", "a"), "2024-02-02", "VLC", "desktop", None).unwrap();
+        stats.record_play(&song("Artist", "Album", "d"), "2024-02-03", "VLC", "desktop", None).unwrap();
+
+        let songs = stats.song_first_plays(&Filter::default().range(date("2024-02-01"), None)).unwrap();
+        let songs: Vec<(&str, &str)> = songs.iter().map(|s| (s.song.title.as_str(), s.first_played.as_str())).collect();
+        assert_eq!(songs, [("a", "2024-01-01"), ("d", "2024-02-03")]);
+
+        let albums = stats.album_first_plays(&Filter::default().range(date("2024-01-03"), None)).unwrap();
+        assert_eq!(albums, [
+            AlbumFirstPlay { artist: "Artist".to_string(), album: "Album".to_string(), first_played: "2024-01-01".to_string() },
+            AlbumFirstPlay { artist: "Artist".to_string(), album: "Other".to_string(), first_played: "2024-01-02".to_string() },
+            AlbumFirstPlay { artist: "Someone".to_string(), album: "Album".to_string(), first_played: "2024-01-03".to_string() },
+        ]);
+    }
+
+    #[test]
+    fn song_days() {
+        let days = stats().song_days(&Filter::default().range(None, date("2024-01-02"))).unwrap();
+
+        let plays: Vec<(&str, u32)> = days.iter().map(|day| (day.date.as_str(), day.plays)).collect();
+        assert_eq!(plays, [("2024-01-01", 2), ("2024-01-02", 1), ("2024-01-02", 1)]);
+    }
+
+    #[test]
+    fn data_version_follows_other_connections() {
+        let path = std::env::temp_dir().join(format!("mpressed-data-version-{}.db", std::process::id()));
+        let client = Stats::open(&path).unwrap();
+        let daemon = Stats::open(&path).unwrap();
+
+        let before = client.data_version().unwrap();
+        client.record_play(&song("Artist", "Album", "a"), "2024-01-01", "", "", None).unwrap();
+        assert_eq!(client.data_version().unwrap(), before);
+
+        daemon.record_play(&song("Artist", "Album", "a"), "2024-01-01", "", "", None).unwrap();
+        assert_ne!(client.data_version().unwrap(), before);
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn players_and_devices() {
+        let stats = stats();
+
+        let players = stats.players(&Filter::default()).unwrap();
+        assert_eq!(players, [
+            SourceStat { source: "VLC".to_string(), plays: 5 },
+            SourceStat { source: "Brave".to_string(), plays: 1 },
+        ]);
+
+        let devices = stats.devices(&Filter::default().player(Some("VLC".to_string()))).unwrap();
+        assert_eq!(devices, [
+            SourceStat { source: "desktop".to_string(), plays: 4 },
+            SourceStat { source: "laptop".to_string(), plays: 1 },
+        ]);
+    }
+}
@@ -0,0 +1,444 @@
+use std::time::Duration;
+use chrono::Local;
+use log::debug;
+use mpris::{Metadata, PlaybackStatus, Player, ProgressTracker};
+use crate::{SongData, Track};
+
+// a position jumping back into the first seconds of the same song means it started over
+pub const RESTART_WINDOW: Duration = Duration::from_secs(3);
+// how long a player may play before publishing metadata and still have that time counted
+pub const LATE_METADATA_MS: i64 = 10000;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlayerState {
+    pub status: PlaybackStatus,
+    // None until the player has published artist, album and title
+    pub track: Option<Track>,
+    pub position: Option<Duration>,
+}
+
+pub trait PlayerSource {
+    // blocks for about a second, None once the player has gone away
+    fn tick(&mut self) -> Option<PlayerState>;
+}
+
+pub trait Clock {
+    fn now_millis(&self) -> i64;
+    fn today(&self) -> String;
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_millis(&self) -> i64 {
+        Local::now().timestamp_millis()
+    }
+
+    fn today(&self) -> String {
+        Local::now().date_naive().to_string()
+    }
+}
+
+// a song that has been listened to for long enough
+#[derive(Clone, Debug, PartialEq)]
+pub struct Play {
+    pub track: Track,
+    pub date: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct Tracker {
+    min_playtime_ms: i64,
+    song: Option<SongData>,
+    position: Option<Duration>,
+    playtime: i64,
+    date: String,
+    written: bool,
+}
+
+impl Tracker {
+    pub fn new(min_playtime_ms: i64, today: String) -> Self {
+        Self {
+            min_playtime_ms,
+            song: None,
+            position: None,
+            playtime: 0,
+            date: today,
+            written: false,
+        }
+    }
+
+    pub fn song(&self) -> Option<&SongData> {
+        self.song.as_ref()
+    }
+
+    pub fn playtime(&self) -> i64 {
+        self.playtime
+    }
+
+    pub fn written(&self) -> bool {
+        self.written
+    }
+
+    // elapsed_ms is how long the tick that produced state took
+    pub fn update<C: Clock>(&mut self, state: &PlayerState, elapsed_ms: i64, clock: &C) -> Option<Play> {
+        if state.status != PlaybackStatus::Playing {
+            return None;
+        }
+
+        let song_current = state.track.as_ref().map(|track| &track.song);
+        let restarted = song_current.is_some() && song_current == self.song.as_ref() && self.restarted(state.position);
+        self.position = state.position;
+
+        if song_current == self.song.as_ref() && !restarted {
+            self.playtime += elapsed_ms;
+        } else if self.song.is_none() && !self.written && self.playtime <= LATE_METADATA_MS {
+            // metadata arrived late, the time spent waiting for it belongs to this song
+            self.song = song_current.cloned();
+            self.playtime += elapsed_ms;
+        } else {
+            self.song = song_current.cloned();
+            self.playtime = 0;
+            self.written = false;
+            self.date = clock.today();
+            return None;
+        }
+
+        let track = state.track.as_ref()?;
+        if self.written || self.playtime < self.min_playtime_ms {
+            return None;
+        }
+
+        self.written = true;
+        Some(Play {
+            track: track.clone(),
+            date: self.date.clone(),
+        })
+    }
+
+    fn restarted(&self, position: Option<Duration>) -> bool {
+        match (self.position, position) {
+            (Some(previous), Some(current)) => current < previous && current < RESTART_WINDOW,
+            _ => false,
+        }
+    }
+}
+
+// follows source until it goes away, calling on_tick after every tick and on_play for every counted play
+pub fn run<P, C, T, F>(source: &mut P, clock: &C, min_playtime_ms: i64, mut on_tick: T, mut on_play: F)
+where
+    P: PlayerSource,
+    C: Clock,
+    T: FnMut(&Tracker, &PlayerState),
+    F: FnMut(Play),
+{
+    let mut tracker = Tracker::new(min_playtime_ms, clock.today());
+
+    loop {
+        debug!("tick: {}, {:?}", tracker.playtime(), tracker.song());
+
+        let last_tick = clock.now_millis();
+        let Some(state) = source.tick() else {
+            break;
+        };
+
+        if let Some(play) = tracker.update(&state, clock.now_millis() - last_tick, clock) {
+            on_play(play);
+        }
+        on_tick(&tracker, &state);
+    }
+}
+
+pub struct MprisSource<'a> {
+    player: &'a Player,
+    progress: ProgressTracker<'a>,
+}
+
+impl<'a> MprisSource<'a> {
+    pub fn new(player: &'a Player) -> Result<Self, mpris::DBusError> {
+        Ok(Self {
+            player,
+            progress: player.track_progress(1000)?,
+        })
+    }
+}
+
+impl PlayerSource for MprisSource<'_> {
+    fn tick(&mut self) -> Option<PlayerState> {
+        let tick = self.progress.tick();
+        let status = self.player.get_playback_status().ok()?;
+
+        Some(PlayerState {
+            status,
+            track: get_track(tick.progress.metadata(), self.player.identity()),
+            position: Some(tick.progress.position()),
+        })
+    }
+}
+
+pub fn get_song_data(data: &Metadata) -> Option<SongData> {
+    Some(SongData {
+        // ISSUE
+        // opus only allows for one artist and joins by ","
+        // other formats join by " / "
+        artist: data.artists()?.join(" / "),
+        album: data.album_name()?.to_string(),
+        title: data.title()?.to_string(),
+        album_artist: data.album_artists().map(|artists| artists.join(" / ")).unwrap_or_default(),
+    })
+}
+
+pub fn get_track(data: &Metadata, identity: &str) -> Option<Track> {
+    Some(Track {
+        song: get_song_data(data)?,
+        url: data.url().map(str::to_string),
+        length: data.length(),
+        player: identity.to_string(),
+    })
+}
+
+// test doubles, for this crate's tests or others with the test-util feature
+#[cfg(any(test, feature = "test-util"))]
+pub mod fake {
+    use std::cell::Cell;
+    use std::collections::VecDeque;
+    use std::rc::Rc;
+    use std::time::Duration;
+    use chrono::DateTime;
+    use mpris::PlaybackStatus;
+    use super::{Clock, PlayerSource, PlayerState};
+    use crate::{SongData, Track};
+
+    // shared between the test and the fake player, which advances it a second per tick
+    #[derive(Clone, Debug, Default)]
+    pub struct FakeClock(Rc<Cell<i64>>);
+
+    impl FakeClock {
+        pub fn new(start_millis: i64) -> Self {
+            Self(Rc::new(Cell::new(start_millis)))
+        }
+
+        pub fn advance(&self, millis: i64) {
+            self.0.set(self.0.get() + millis);
+        }
+    }
+
+    impl Clock for FakeClock {
+        fn now_millis(&self) -> i64 {
+            self.0.get()
+        }
+
+        fn today(&self) -> String {
+            DateTime::from_timestamp_millis(self.0.get())
+                .unwrap_or_default()
+                .date_naive()
+                .to_string()
+        }
+    }
+
+    // plays back a script of one second ticks
+    #[derive(Debug)]
+    pub struct FakePlayer {
+        identity: String,
+        clock: FakeClock,
+        script: VecDeque<PlayerState>,
+        last_song: Option<SongData>,
+        position: Duration,
+    }
+
+    impl FakePlayer {
+        pub fn new(identity: &str, clock: &FakeClock) -> Self {
+            Self {
+                identity: identity.to_string(),
+                clock: clock.clone(),
+                script: VecDeque::new(),
+                last_song: None,
+                position: Duration::ZERO,
+            }
+        }
+
+        // continues song if it was the last one scripted, otherwise starts it
+        pub fn play(mut self, song: &SongData, seconds: u64) -> Self {
+            if self.last_song.as_ref() != Some(song) {
+                self.position = Duration::ZERO;
+            }
+            self.last_song = Some(song.clone());
+            self.push(PlaybackStatus::Playing, true, seconds)
+        }
+
+        // starts song from the beginning even if it was already playing
+        pub fn replay(mut self, song: &SongData, seconds: u64) -> Self {
+            self.position = Duration::ZERO;
+            self.last_song = Some(song.clone());
+            self.push(PlaybackStatus::Playing, true, seconds)
+        }
+
+        // playing, but artist, album or title haven't been published yet
+        pub fn play_without_metadata(mut self, seconds: u64) -> Self {
+            self.position = Duration::ZERO;
+            self.last_song = None;
+            self.push(PlaybackStatus::Playing, false, seconds)
+        }
+
+        pub fn pause(self, seconds: u64) -> Self {
+            self.push(PlaybackStatus::Paused, true, seconds)
+        }
+
+        fn push(mut self, status: PlaybackStatus, with_metadata: bool, seconds: u64) -> Self {
+            for _ in 0..seconds {
+                if status == PlaybackStatus::Playing {
+                    self.position += Duration::from_secs(1);
+                }
+
+                let track = self.last_song.clone()
+                    .filter(|_| with_metadata)
+                    .map(|song| Track {
+                        song,
+                        url: None,
+                        length: None,
+                        player: self.identity.clone(),
+                    });
+
+                self.script.push_back(PlayerState {
+                    status,
+                    track,
+                    position: Some(self.position),
+                });
+            }
+            self
+        }
+    }
+
+    impl PlayerSource for FakePlayer {
+        fn tick(&mut self) -> Option<PlayerState> {
+            let state = self.script.pop_front()?;
+            self.clock.advance(1000);
+            Some(state)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fake::{FakeClock, FakePlayer};
+    use super::*;
+
+    const MIN_PLAYTIME_MS: i64 = 60000;
+
+    // 2024-01-01T00:00:00Z
+    const START: i64 = 1704067200000;
+
+    fn song(title: &str) -> SongData {
+        SongData {
+            artist: "Artist".to_string(),
+            album: "Album".to_string(),
+            title: title.to_string(),
+            ..SongData::default()
+        }
+    }
+
+    fn plays(player: FakePlayer, clock: &FakeClock) -> Vec<Play> {
+        let mut player = player;
+        let mut plays = vec!();
+        run(&mut player, clock, MIN_PLAYTIME_MS, |_, _| {}, |play| plays.push(play));
+        plays
+    }
+
+    fn titles(plays: &[Play]) -> Vec<&str> {
+        plays.iter().map(|play| play.track.song.title.as_str()).collect()
+    }
+
+    #[test]
+    fn counts_after_min_playtime() {
+        let clock = FakeClock::new(START);
+        let player = FakePlayer::new("Fake", &clock).play(&song("a"), 60);
+        let plays = plays(player, &clock);
+
+        assert_eq!(titles(&plays), ["a"]);
+        assert_eq!(plays[0].date, "2024-01-01");
+        assert_eq!(plays[0].track.player, "Fake");
+    }
+
+    #[test]
+    fn skipped_song_is_not_counted() {
+        let clock = FakeClock::new(START);
+        let player = FakePlayer::new("Fake", &clock)
+            .play(&song("a"), 30)
+            .play(&song("b"), 90);
+
+        assert_eq!(titles(&plays(player, &clock)), ["b"]);
+    }
+
+    #[test]
+    fn counted_once_however_long_it_plays() {
+        let clock = FakeClock::new(START);
+        let player = FakePlayer::new("Fake", &clock).play(&song("a"), 600);
+
+        assert_eq!(plays(player, &clock).len(), 1);
+    }
+
+    #[test]
+    fn pause_at_59s_then_resume() {
+        let clock = FakeClock::new(START);
+        let player = FakePlayer::new("Fake", &clock)
+            .play(&song("a"), 59)
+            .pause(300)
+            .play(&song("a"), 1);
+
+        assert_eq!(titles(&plays(player, &clock)), ["a"]);
+    }
+
+    #[test]
+    fn paused_time_does_not_count() {
+        let clock = FakeClock::new(START);
+        let player = FakePlayer::new("Fake", &clock)
+            .play(&song("a"), 30)
+            .pause(300);
+
+        assert!(plays(player, &clock).is_empty());
+    }
+
+    #[test]
+    fn same_song_twice() {
+        let clock = FakeClock::new(START);
+        let player = FakePlayer::new("Fake", &clock)
+            .play(&song("a"), 120)
+            .replay(&song("a"), 120);
+
+        assert_eq!(titles(&plays(player, &clock)), ["a", "a"]);
+    }
+
+    #[test]
+    fn metadata_arrives_late() {
+        let clock = FakeClock::new(START);
+        let player = FakePlayer::new("Fake", &clock)
+            .play_without_metadata(5)
+            .play(&song("a"), 55);
+
+        assert_eq!(titles(&plays(player, &clock)), ["a"]);
+    }
+
+    #[test]
+    fn long_wait_for_metadata_is_not_counted() {
+        let clock = FakeClock::new(START);
+        let player = FakePlayer::new("Fake", &clock)
+            .play_without_metadata(300)
+            .play(&song("a"), 30);
+
+        assert!(plays(player, &clock).is_empty());
+    }
+
+    #[test]
+    fn date_is_when_the_song_started() {
+        // 2024-01-01T23:59:30Z
+        let clock = FakeClock::new(START + 86370000);
+        let player = FakePlayer::new("Fake", &clock)
+            .play(&song("a"), 10)
+            .play(&song("b"), 90);
+        let plays = plays(player, &clock);
+
+        assert_eq!(titles(&plays), ["b"]);
+        assert_eq!(plays[0].date, "2024-01-01");
+    }
+}
@@ -0,0 +1,289 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::time::Duration;
+use chrono::{Datelike, NaiveDate, TimeDelta};
+use crate::{format_duration, SongData};
+use crate::period::{period_totals, Period, PeriodStat};
+use crate::stats::{AlbumStat, ArtistStat, DateStat, Filter, SongStat, Stats};
+
+// how long each of the top lists is
+pub const TOP: usize = 5;
+
+// consecutive days with plays, both ends inclusive
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Streak {
+    pub first: NaiveDate,
+    pub last: NaiveDate,
+}
+
+impl Streak {
+    pub fn days(&self) -> i64 {
+        (self.last - self.first).num_days() + 1
+    }
+}
+
+// the song played the most in a single ISO week
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Obsession {
+    pub song: SongData,
+    // 2024-W11
+    pub week: String,
+    pub plays: u32,
+}
+
+// a heading and its lines, laid out the same way in the TUI, text and Markdown
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Section {
+    pub title: String,
+    pub lines: Vec<String>,
+}
+
+// a year of listening summed up, None and empty lists when nothing was played
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Wrapped {
+    pub year: i32,
+    pub plays: u32,
+    pub listening_time: Duration,
+    pub top_songs: Vec<SongStat>,
+    pub top_artists: Vec<ArtistStat>,
+    pub top_albums: Vec<AlbumStat>,
+    pub top_day: Option<DateStat>,
+    pub top_month: Option<PeriodStat>,
+    // artists first played this year, most played first
+    pub new_artists: Vec<ArtistStat>,
+    pub longest_streak: Option<Streak>,
+    pub obsession: Option<Obsession>,
+}
+
+// the filter's range and limit are replaced by the year, everything else narrows it down
+pub fn wrapped(stats: &Stats, year: i32, filter: &Filter) -> rusqlite::Result<Wrapped> {
+    let filter = filter.clone()
+        .range(NaiveDate::from_yo_opt(year, 1), NaiveDate::from_ymd_opt(year, 12, 31))
+        .limit(None);
+    let top = filter.clone().limit(Some(TOP));
+
+    let dates = stats.date_totals(&filter)?;
+    let artists = stats.top_artists(&filter)?;
+
+    let discovered = stats.artist_first_plays(&filter)?
+        .into_iter()
+        .filter(|first| first.first_played.parse::<NaiveDate>().is_ok_and(|date| date.year() == year))
+        .map(|first| first.artist)
+        .collect::<HashSet<String>>();
+
+    Ok(Wrapped {
+        year,
+        plays: dates.iter().map(|date| date.plays).sum(),
+        listening_time: dates.iter().map(|date| date.listening_time).sum(),
+        top_songs: stats.top_songs(&top)?,
+        top_artists: artists.iter().take(TOP).cloned().collect(),
+        top_albums: stats.top_albums(&top)?,
+        // date_totals is most played first
+        top_day: dates.first().cloned(),
+        top_month: period_totals(&dates, Period::Month, None, None)
+            .into_iter()
+            .min_by_key(|month| Reverse(month.plays)),
+        new_artists: artists.into_iter()
+            .filter(|artist| discovered.contains(&artist.artist))
+            .collect(),
+        longest_streak: longest_streak(dates.iter().filter_map(|date| date.date.parse().ok())),
+        obsession: obsession(stats, &filter)?,
+    })
+}
+
+// the earliest one when several are as long
+fn longest_streak(days: impl Iterator<Item = NaiveDate>) -> Option<Streak> {
+    let days: BTreeSet<NaiveDate> = days.collect();
+
+    let mut streaks: Vec<Streak> = vec!();
+    for day in days {
+        match streaks.last_mut() {
+            Some(streak) if streak.last + TimeDelta::days(1) == day => streak.last = day,
+            _ => streaks.push(Streak { first: day, last: day }),
+        }
+    }
+
+    streaks.into_iter().min_by_key(|streak| Reverse(streak.days()))
+}
+
+fn obsession(stats: &Stats, filter: &Filter) -> rusqlite::Result<Option<Obsession>> {
+    let mut weeks: HashMap<(i64, NaiveDate), u32> = HashMap::new();
+    for day in stats.song_days(filter)? {
+        let Ok(date) = day.date.parse::<NaiveDate>() else {
+            continue;
+        };
+        *weeks.entry((day.id, Period::Week.start(date))).or_default() += day.plays;
+    }
+
+    // ties go to the earlier week
+    let Some(((id, start), plays)) = weeks.into_iter().min_by_key(|&((id, start), plays)| (Reverse(plays), start, id)) else {
+        return Ok(None);
+    };
+
+    Ok(stats.top_songs(&filter.clone().song(Some(id)))?
+        .into_iter()
+        .next()
+        .map(|song| Obsession {
+            song: song.song,
+            week: Period::Week.label(start),
+            plays,
+        }))
+}
+
+fn plays(plays: u32) -> String {
+    format!("{} play{}", plays, if plays == 1 { "" } else { "s" })
+}
+
+impl Wrapped {
+    pub fn title(&self) -> String {
+        format!("Wrapped {}", self.year)
+    }
+
+    pub fn sections(&self) -> Vec<Section> {
+        let section = |title: &str, lines: Vec<String>| Section { title: title.to_string(), lines };
+        let ranked = |i: usize, name: String, count: u32| format!("{}. {} ({})", i + 1, name, plays(count));
+
+        if self.plays == 0 {
+            return vec!(section("Listening", vec!(format!("Nothing was played in {}", self.year))));
+        }
+
+        let mut listening = vec!(plays(self.plays));
+        if !self.listening_time.is_zero() {
+            listening.push(format!("{} listened", format_duration(self.listening_time)));
+        }
+
+        let mut highlights = vec!();
+        if let Some(day) = &self.top_day {
+            highlights.push(format!("Most played day: {} ({})", day.date, plays(day.plays)));
+        }
+        if let Some(month) = &self.top_month {
+            highlights.push(format!("Most played month: {} ({})", month.label, plays(month.plays)));
+        }
+        if let Some(streak) = &self.longest_streak {
+            highlights.push(format!("Longest streak: {} day{}, {} to {}", streak.days(), if streak.days() == 1 { "" } else { "s" }, streak.first, streak.last));
+        }
+        if let Some(obsession) = &self.obsession {
+            highlights.push(format!("Biggest obsession: {} - {}, {} in {}", obsession.song.title, obsession.song.artist, plays(obsession.plays), obsession.week));
+        }
+
+        let mut new_artists = vec!(format!("{} discovered", self.new_artists.len()));
+        new_artists.extend(self.new_artists.iter()
+            .take(TOP)
+            .enumerate()
+            .map(|(i, artist)| ranked(i, artist.artist.clone(), artist.plays)));
+
+        vec!(
+            section("Listening", listening),
+            section("Top songs", self.top_songs.iter()
+                .enumerate()
+                .map(|(i, song)| ranked(i, format!("{} - {}", song.song.title, song.song.artist), song.plays))
+                .collect()),
+            section("Top artists", self.top_artists.iter()
+                .enumerate()
+                .map(|(i, artist)| ranked(i, artist.artist.clone(), artist.plays))
+                .collect()),
+            section("Top albums", self.top_albums.iter()
+                .enumerate()
+                .map(|(i, album)| ranked(i, format!("{} - {}", album.album, album.artist), album.plays))
+                .collect()),
+            section("Highlights", highlights),
+            section("New artists", new_artists),
+        )
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut text = format!("{}\n", self.title());
+        for section in self.sections() {
+            text.push_str(&format!("\n{}\n", section.title));
+            for line in section.lines {
+                text.push_str(&format!("  {}\n", line));
+            }
+        }
+        text
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut markdown = format!("# {}\n", self.title());
+        for section in self.sections() {
+            markdown.push_str(&format!("\n## {}\n\n", section.title));
+            for line in section.lines {
+                markdown.push_str(&format!("- {}\n", line));
+            }
+        }
+        markdown
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn song(artist: &str, title: &str) -> SongData {
+        SongData {
+            artist: artist.to_string(),
+            album: "Album".to_string(),
+            title: title.to_string(),
+            ..SongData::default()
+        }
+    }
+
+    fn date(text: &str) -> NaiveDate {
+        text.parse().unwrap()
+    }
+
+    // Old was first played in 2023, New in 2024, b is played four times in the week of 2024-03-11
+    fn stats() -> Stats {
+        let stats = Stats::open_in_memory().unwrap();
+        let a = song("Old", "a");
+        let b = song("New", "b");
+
+        stats.record_play(&a, "2023-12-31", "VLC", "desktop", None).unwrap();
+        stats.record_play(&a, "2024-01-01", "VLC", "desktop", None).unwrap();
+        stats.record_play(&a, "2024-01-02", "VLC", "desktop", Some(Duration::from_secs(120))).unwrap();
+        stats.record_play(&a, "2024-01-03", "VLC", "desktop", None).unwrap();
+        stats.record_play(&b, "2024-03-11", "VLC", "desktop", None).unwrap();
+        stats.record_play(&b, "2024-03-11", "VLC", "laptop", None).unwrap();
+        stats.record_play(&b, "2024-03-12", "VLC", "desktop", None).unwrap();
+        stats.record_play(&b, "2024-03-14", "VLC", "desktop", None).unwrap();
+        stats
+    }
+
+    #[test]
+    fn sums_up_the_year() {
+        let wrapped = wrapped(&stats(), 2024, &Filter::default()).unwrap();
+
+        assert_eq!(wrapped.plays, 7);
+        assert_eq!(wrapped.listening_time, Duration::from_secs(360));
+        assert_eq!(wrapped.top_songs.iter().map(|song| song.song.title.as_str()).collect::<Vec<_>>(), ["b", "a"]);
+        assert_eq!(wrapped.top_day.map(|day| (day.date, day.plays)), Some(("2024-03-11".to_string(), 2)));
+        assert_eq!(wrapped.top_month.map(|month| month.label), Some("2024-03".to_string()));
+        assert_eq!(wrapped.new_artists.iter().map(|artist| artist.artist.as_str()).collect::<Vec<_>>(), ["New"]);
+    }
+
+    #[test]
+    fn streak_and_obsession() {
+        let wrapped = wrapped(&stats(), 2024, &Filter::default()).unwrap();
+
+        assert_eq!(wrapped.longest_streak, Some(Streak { first: date("2024-01-01"), last: date("2024-01-03") }));
+        assert_eq!(wrapped.obsession, Some(Obsession { song: song("New", "b"), week: "2024-W11".to_string(), plays: 4 }));
+    }
+
+    #[test]
+    fn longest_streak_ties_go_to_the_earliest() {
+        let days = ["2024-01-05", "2024-01-01", "2024-01-02", "2024-01-04"].map(date);
+        assert_eq!(longest_streak(days.into_iter()), Some(Streak { first: date("2024-01-01"), last: date("2024-01-02") }));
+        assert_eq!(longest_streak([].into_iter()), None);
+    }
+
+    #[test]
+    fn reports() {
+        let stats = stats();
+
+        let markdown = wrapped(&stats, 2024, &Filter::default()).unwrap().to_markdown();
+        assert!(markdown.starts_with("# Wrapped 2024\n\n## Listening\n\n- 7 plays\n- 6m 00s listened\n"));
+        assert!(markdown.contains("\n## New artists\n\n- 1 discovered\n- 1. New (4 plays)\n"));
+
+        let text = wrapped(&stats, 2022, &Filter::default()).unwrap().to_text();
+        assert_eq!(text, "Wrapped 2022\n\nListening\n  Nothing was played in 2022\n");
+    }
+}
@@ -0,0 +1,228 @@
+// runs mpressed-daemon against mpressed-fake-player on a private session bus.
+// these need dbus-daemon, so they are ignored unless run with cargo test -- --ignored
+
+use std::fs::{create_dir_all, read_to_string, write};
+use std::io::{BufRead, BufReader};
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+use chrono::Local;
+use rusqlite::Connection;
+
+// how long the daemon gets to catch up with the player
+const DEADLINE: Duration = Duration::from_secs(10);
+
+fn wait_until(what: &str, mut done: impl FnMut() -> bool) {
+    let start = Instant::now();
+    while !done() {
+        assert!(start.elapsed() < DEADLINE, "timed out waiting until {}", what);
+        sleep(Duration::from_millis(100));
+    }
+}
+
+struct Bus {
+    daemon: Child,
+    address: String,
+}
+
+impl Bus {
+    fn start() -> Self {
+        let mut daemon = Command::new("dbus-daemon")
+            .args(["--session", "--nofork", "--print-address=1"])
+            .stdout(Stdio::piped())
+            .spawn()
+            .expect("Failed to start dbus-daemon");
+
+        let mut address = String::new();
+        BufReader::new(daemon.stdout.take().unwrap()).read_line(&mut address).unwrap();
+
+        Self {
+            daemon,
+            address: address.trim().to_string(),
+        }
+    }
+}
+
+impl Drop for Bus {
+    fn drop(&mut self) {
+        let _ = self.daemon.kill();
+        let _ = self.daemon.wait();
+    }
+}
+
+struct Harness {
+    bus: Bus,
+    config_dir: PathBuf,
+    daemon: Child,
+}
+
+impl Harness {
+    fn start(name: &str, config: &str) -> Self {
+        let bus = Bus::start();
+
+        let config_dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+        let _ = std::fs::remove_dir_all(&config_dir);
+        create_dir_all(&config_dir).unwrap();
+        write(config_dir.join("config"), config).unwrap();
+
+        let daemon = Command::new(env!("CARGO_BIN_EXE_mpressed-daemon"))
+            .env("DBUS_SESSION_BUS_ADDRESS", &bus.address)
+            .env("MPRESSED_CONFIG_DIR", &config_dir)
+            .stdout(Stdio::null())
+            .spawn()
+            .unwrap();
+
+        Self {
+            bus,
+            config_dir,
+            daemon,
+        }
+    }
+
+    fn spawn_player(&self, script: &[&str]) -> Child {
+        Command::new(env!("CARGO_BIN_EXE_mpressed-fake-player"))
+            .env("DBUS_SESSION_BUS_ADDRESS", &self.bus.address)
+            .args(["--identity", "Fake Player"])
+            .args(script)
+            .spawn()
+            .unwrap()
+    }
+
+    // blocks until the script has finished and the player has quit
+    fn play(&self, script: &[&str]) {
+        let status = self.spawn_player(script).wait().unwrap();
+
+        assert!(status.success());
+        // anything the daemon records is written before it notices the player is gone
+        self.wait_for_player_gone();
+    }
+
+    fn now_playing_path(&self) -> PathBuf {
+        self.config_dir.join("now_playing")
+    }
+
+    fn wait_for_player_gone(&self) {
+        wait_until("the daemon notices the player is gone", || !self.now_playing_path().exists());
+    }
+
+    fn plays(&self) -> Vec<(String, String, u32, String, String)> {
+        Connection::open(self.config_dir.join("mpressed.db"))
+            .unwrap()
+            .prepare("SELECT title, date, plays, player, device FROM song_data JOIN song_plays ON song_data.id = song_plays.id ORDER BY title")
+            .unwrap()
+            .query_map((), |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?)))
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect()
+    }
+}
+
+impl Drop for Harness {
+    fn drop(&mut self) {
+        let _ = self.daemon.kill();
+        let _ = self.daemon.wait();
+    }
+}
+
+const CONFIG: &str = "[daemon]
+players = Fake Player
+min_playtime = 3s
+device = ci
+";
+
+// a leading pause gives the daemon time to find the player
+#[test]
+#[ignore = "needs dbus-daemon"]
+fn records_songs_played_long_enough() {
+    // the day the play is recorded on, taken once before anything runs
+    let today = Local::now().date_naive().to_string();
+    let harness = Harness::start("records_songs_played_long_enough", CONFIG);
+
+    harness.play(&[
+        "pause", "3",
+        "play", "Artist", "Album", "Counted", "6",
+        "play", "Artist", "Album", "Skipped", "1",
+    ]);
+
+    let plays = harness.plays();
+    assert_eq!(plays.len(), 1, "{:?}", plays);
+
+    let (title, date, count, player, device) = &plays[0];
+    assert_eq!(title, "Counted");
+    assert_eq!(date, &today);
+    assert_eq!(*count, 1);
+    assert_eq!(player, "Fake Player");
+    assert_eq!(device, "ci");
+}
+
+#[test]
+#[ignore = "needs dbus-daemon"]
+fn pause_does_not_count_towards_playtime() {
+    let harness = Harness::start("pause_does_not_count_towards_playtime", CONFIG);
+
+    harness.play(&[
+        "pause", "3",
+        "play", "Artist", "Album", "Paused", "2",
+        "pause", "4",
+    ]);
+
+    assert!(harness.plays().is_empty());
+}
+
+#[test]
+#[ignore = "needs dbus-daemon"]
+fn excluded_songs_are_logged_not_recorded() {
+    let config = format!("{}\n[exclude]\ndomain = youtube.com\n", CONFIG);
+    let harness = Harness::start("excluded_songs_are_logged_not_recorded", &config);
+
+    harness.play(&[
+        "pause", "3",
+        "url", "https://www.youtube.com/watch?v=0",
+        "play", "Artist", "Album", "Video", "6",
+    ]);
+
+    assert!(harness.plays().is_empty());
+
+    let log = read_to_string(harness.config_dir.join("excluded.log")).unwrap();
+    assert!(log.contains("domain = youtube.com"), "{}", log);
+    assert!(log.contains("Video"), "{}", log);
+}
+
+#[test]
+#[ignore = "needs dbus-daemon"]
+fn replayed_song_counts_twice() {
+    let harness = Harness::start("replayed_song_counts_twice", CONFIG);
+
+    harness.play(&[
+        "pause", "3",
+        "play", "Artist", "Album", "Twice", "6",
+        "replay", "6",
+    ]);
+
+    let plays = harness.plays();
+    assert_eq!(plays.len(), 1, "{:?}", plays);
+    assert_eq!(plays[0].2, 2);
+}
+
+#[test]
+#[ignore = "needs dbus-daemon"]
+fn now_playing_follows_the_player() {
+    let harness = Harness::start("now_playing_follows_the_player", CONFIG);
+
+    let mut player = harness.spawn_player(&[
+        "pause", "3",
+        "play", "Artist", "Album", "Current", "8",
+    ]);
+
+    let mut now_playing = String::new();
+    wait_until("the song is counted", || {
+        now_playing = read_to_string(harness.now_playing_path()).unwrap_or_default();
+        now_playing.contains("counted = true")
+    });
+    assert!(now_playing.contains("title = Current"), "{}", now_playing);
+    assert!(now_playing.contains("player = Fake Player"), "{}", now_playing);
+
+    assert!(player.wait().unwrap().success());
+    harness.wait_for_player_gone();
+}